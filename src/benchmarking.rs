@@ -17,12 +17,111 @@
 
 use super::*;
 
-use crate::{Pallet as Utxo, Transaction, TransactionInput, TransactionOutput};
+use crate::{
+    zk::{self, ZkProof},
+    Pallet as Utxo, Transaction, TransactionInput, TransactionOutput,
+};
+use ark_bls12_381::{Bls12_381, Fr};
+use ark_groth16::{create_random_proof, generate_random_parameters, ProvingKey};
+use ark_relations::{
+    lc,
+    r1cs::{ConstraintSynthesizer, ConstraintSystemRef, SynthesisError},
+};
+use ark_serialize::CanonicalSerialize;
+use ark_std::test_rng;
 use codec::Encode;
 use frame_benchmarking::{benchmarks, whitelisted_caller};
+use frame_support::sp_io::{crypto, hashing::sha2_256};
 use frame_system::{EventRecord, RawOrigin};
-use hex_literal::hex;
-use sp_core::{sp_std::vec, sr25519::Public, testing::SR25519, H256, H512};
+use sp_core::{
+    sp_std::{vec, vec::Vec},
+    sr25519::Public,
+    testing::SR25519,
+    H256, H512,
+};
+
+// Most authorities the rewards are split among in `disperse_reward`.
+const MAX_AUTHORITIES: u32 = 100;
+
+// A transaction consolidating `inputs` utxos of 1_000_000 each, all locked to the same key, which
+// pays the minimum fee.
+fn consolidation_tx<T: Config>(inputs: u32) -> Transaction {
+    let pub_key = crypto::sr25519_generate(SR25519, None);
+    let mut tx = Transaction {
+        inputs: (0..inputs)
+            .map(|index| {
+                let outpoint = OutPoint::new(H256::repeat_byte(1), index as u64);
+                UtxoStore::<T>::insert(
+                    outpoint,
+                    Some(TransactionOutput::new(1_000_000, H256::from(pub_key))),
+                );
+                TransactionInput::new(outpoint, vec![])
+            })
+            .collect(),
+        outputs: vec![TransactionOutput::new(
+            1_000_000 * inputs as Value,
+            H256::from(pub_key),
+        )],
+    };
+
+    // pay the runtime's minimum fee, for the size the transaction has once signed
+    for input in tx.inputs.iter_mut() {
+        input.witness = vec![vec![0; 64]];
+    }
+    tx.outputs[0].value -= minimum_fee::<T>(&tx);
+
+    for index in 0..tx.inputs.len() {
+        let message = signature_message(&tx, index, SigHash::All).unwrap();
+        let signature = crypto::sr25519_sign(SR25519, &pub_key, &message).unwrap();
+        tx.inputs[index].witness = vec![signature.0.to_vec()];
+    }
+    tx
+}
+
+// Knows a square root of its second public input, after the message hash.
+struct SquareRootCircuit {
+    message: Fr,
+    root: Fr,
+}
+
+impl ConstraintSynthesizer<Fr> for SquareRootCircuit {
+    fn generate_constraints(self, cs: ConstraintSystemRef<Fr>) -> Result<(), SynthesisError> {
+        cs.new_input_variable(|| Ok(self.message))?;
+        let square = cs.new_input_variable(|| Ok(self.root * self.root))?;
+        let root = cs.new_witness_variable(|| Ok(self.root))?;
+        cs.enforce_constraint(lc!() + root, lc!() + root, lc!() + square)
+    }
+}
+
+fn serialize(item: &impl CanonicalSerialize) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    item.serialize(&mut bytes).unwrap();
+    bytes
+}
+
+// The root proven by `zk_item`.
+const ZK_ROOT: u64 = 3;
+
+// The public inputs of the square root circuit for the square of `ZK_ROOT`.
+fn zk_statement() -> Vec<Vec<u8>> {
+    vec![serialize(&(Fr::from(ZK_ROOT) * Fr::from(ZK_ROOT)))]
+}
+
+// The witness item proving the square root circuit for `message`.
+fn zk_item(proving_key: &ProvingKey<Bls12_381>, message: &[u8]) -> Vec<u8> {
+    let root = Fr::from(ZK_ROOT);
+    let circuit = SquareRootCircuit {
+        message: zk::message_input(message),
+        root,
+    };
+    let proof = create_random_proof(circuit, proving_key, &mut test_rng()).unwrap();
+    ZkProof {
+        verifying_key: serialize(&proving_key.vk),
+        proof: serialize(&proof),
+        public_inputs: zk_statement(),
+    }
+    .encode()
+}
 
 fn assert_last_event<T: Config>(generic_event: <T as Config>::Event) {
     let events = frame_system::Pallet::<T>::events();
@@ -43,79 +142,250 @@ benchmarks! {
         ]);
         let alice_pub_key = Public::from_h256(alice_h256.clone());
 
-        let genesis_utxo = hex!("931fe49afe365072e71771cd99e13cfb54fa28fad479e23556ff9de6a3dd19a9");
-        let genesis_utxo = H256::from(genesis_utxo);
+        // the only genesis utxo of the mock runtime
+        let genesis_utxo = Transaction {
+            inputs: vec![],
+            outputs: vec![TransactionOutput::new(100, alice_h256)],
+        }.outpoint(0);
 
          let mut tx = Transaction {
             inputs: vec![TransactionInput {
                 outpoint: genesis_utxo,
-                sig_script: H512::zero(),
+                witness: vec![],
+                sighash: SigHash::All,
             }],
             outputs: vec![TransactionOutput::new(50, alice_h256)],
         };
 
-        let alice_sig = frame_support::sp_io::crypto::sr25519_sign(SR25519, &alice_pub_key, &tx.encode()).unwrap();
+        let message = signature_message(&tx, 0, SigHash::All).unwrap();
+        let alice_sig = crypto::sr25519_sign(SR25519, &alice_pub_key, &message).unwrap();
 
-        tx.inputs[0].sig_script = H512::from(alice_sig);
+        tx.inputs[0].witness = vec![alice_sig.0.to_vec()];
 
-        let caller: T::AccountId = whitelisted_caller();
-    }: spend(RawOrigin::Signed(caller),tx.clone())
+    }: spend(RawOrigin::None, tx.clone())
     verify {
         assert_last_event::<T>(Event::TransactionSuccess(tx).into());
-        assert_eq!(RewardTotal::<T>::get(),50u128);
+        assert_eq!(RewardTotal::<T>::get(TokenType::MLT as TokenID), 50u128);
         assert!(!UtxoStore::<T>::contains_key(genesis_utxo));
     }
 
-    runtime_spend {
-        /// ran using mintlayer-node.
-        // 0x76584168d10a20084082ed80ec71e2a783abbb8dd6eb9d4893b089228498e9ff
-        let alice_h256 = H256::from([
-            212, 53, 147, 199, 21, 253, 211, 28, 97, 20, 26,
-            189, 4, 169, 159, 214,130, 44, 133,88, 133, 76,
-            205, 227, 154, 86, 132, 231, 165, 109, 162, 125]
-        );
-        let alice_pub_key = Public::from_h256(alice_h256.clone());
+    // signatures verified one at a time, as in the transaction pool
+    spend_inputs {
+        let i in 1 .. 100;
+        let tx = consolidation_tx::<T>(i);
+    }: spend(RawOrigin::None, tx.clone())
+    verify {
+        assert_last_event::<T>(Event::TransactionSuccess(tx).into());
+    }
 
-        let genesis_utxo = H256::from([
-             81, 21, 116, 75, 236, 124, 214, 180, 35, 127, 81,
-            208, 154, 106, 21, 216, 89, 10, 92, 139, 45, 15,
-            227, 227, 206, 59, 82, 197, 34, 147, 181, 76]
-        );
+    // signatures verified together, as during block import. Only compared with `spend_inputs`:
+    // the batch spreads the checks over other threads, so the weight does not count on it.
+    spend_inputs_batched {
+        let i in 1 .. 100;
+        let tx = consolidation_tx::<T>(i);
+    }: {
+        crypto::start_batch_verify();
+        Utxo::<T>::spend(RawOrigin::None.into(), tx.clone())?;
+        assert!(crypto::finish_batch_verify());
+    }
+    verify {
+        assert_last_event::<T>(Event::TransactionSuccess(tx).into());
+    }
 
-        // 0x8eaf04151687736326c9fea17e25fc5287613693c912909cb226aa4794f26a48
-        let bob_h256 = H256::from([
-            142, 175, 4, 21, 22, 135, 115, 99, 38, 201, 254, 161,
-            126, 37, 252, 82, 135, 97, 54, 147, 201, 18, 144, 156,
-            178, 38, 170, 71, 148, 242, 106, 72]
+    // the witness grows with the preimage of an htlc, which is hashed along with the rest
+    spend_witness {
+        let w in 64 .. MAX_WITNESS_SIZE as u32;
+        let pub_key = crypto::sr25519_generate(SR25519, None);
+        let preimage = vec![1u8; w as usize - 64];
+        let outpoint = OutPoint::new(H256::repeat_byte(1), 0);
+        UtxoStore::<T>::insert(
+            outpoint,
+            Some(TransactionOutput::new_htlc(
+                1_000_000,
+                H256::from(pub_key),
+                H256::from(pub_key),
+                H256::from(sha2_256(&preimage)),
+                0,
+            )),
         );
-        let bob_pub_key = Public::from_h256(bob_h256.clone());
-
-        // 0x52ffd490c6b266edc96278e7dee680f0bc2454653f2eab823b8d2ec13289d770c7e0a041f32039b7f73b393f3bdd7b09295b56610b59e2165e3d34d83c9ca98f
-        let alice_sigscript = H512::from([
-            82, 255, 212, 144, 198, 178, 102, 237, 201, 98, 120,
-            231, 222, 230, 128, 240, 188, 36, 84, 101, 63, 46,
-            171, 130, 59, 141, 46, 193, 50, 137, 215, 112, 199,
-            224, 160, 65, 243, 32, 57, 183, 247, 59, 57, 63, 59,
-            221, 123, 9, 41, 91, 86, 97, 11, 89, 226, 22, 94, 61,
-            52, 216, 60, 156, 169, 143]
+
+        let mut tx = Transaction {
+            inputs: vec![TransactionInput::new(outpoint, vec![vec![0; 64], preimage.clone()])],
+            outputs: vec![TransactionOutput::new(1_000_000, H256::from(pub_key))],
+        };
+        tx.outputs[0].value -= minimum_fee::<T>(&tx);
+
+        let message = signature_message(&tx, 0, SigHash::All).unwrap();
+        let signature = crypto::sr25519_sign(SR25519, &pub_key, &message).unwrap();
+        tx.inputs[0].witness = vec![signature.0.to_vec(), preimage];
+    }: spend(RawOrigin::None, tx.clone())
+    verify {
+        assert_last_event::<T>(Event::TransactionSuccess(tx).into());
+    }
+
+    // every input is a ZkSnark output, spent with its own proof
+    spend_zk {
+        let z in 1 .. MAX_ZK_INPUTS as u32;
+        let circuit = SquareRootCircuit {
+            message: Fr::from(0u64),
+            root: Fr::from(0u64),
+        };
+        let proving_key =
+            generate_random_parameters::<Bls12_381, _, _>(circuit, &mut test_rng()).unwrap();
+        let verifying_key = serialize(&proving_key.vk);
+
+        let mut tx = Transaction {
+            inputs: (0..z)
+                .map(|index| {
+                    let outpoint = OutPoint::new(H256::repeat_byte(1), index as u64);
+                    UtxoStore::<T>::insert(
+                        outpoint,
+                        Some(TransactionOutput::new_zk(
+                            1_000_000,
+                            &verifying_key,
+                            &zk_statement(),
+                        )),
+                    );
+                    TransactionInput::new(outpoint, vec![zk_item(&proving_key, &[])])
+                })
+                .collect(),
+            outputs: vec![TransactionOutput::new(
+                1_000_000 * z as Value,
+                H256::repeat_byte(2),
+            )],
+        };
+        // proofs of another message have the same size
+        tx.outputs[0].value -= minimum_fee::<T>(&tx);
+
+        for index in 0..tx.inputs.len() {
+            let message = signature_message(&tx, index, SigHash::All).unwrap();
+            tx.inputs[index].witness = vec![zk_item(&proving_key, &message)];
+        }
+    }: spend(RawOrigin::None, tx.clone())
+    verify {
+        assert_last_event::<T>(Event::TransactionSuccess(tx).into());
+    }
+
+    // a single multisig input, with a signature of each of its keys
+    spend_multisig {
+        let c in 1 .. MAX_MULTISIG_KEYS as u32;
+        let keys: Vec<Public> = (0..c).map(|_| crypto::sr25519_generate(SR25519, None)).collect();
+        let outpoint = OutPoint::new(H256::repeat_byte(1), 0);
+        UtxoStore::<T>::insert(
+            outpoint,
+            Some(TransactionOutput::new_multisig(
+                1_000_000,
+                c,
+                keys.iter().map(|key| H256::from(*key)).collect(),
+            )),
         );
 
         let mut tx = Transaction {
-            inputs: vec![ TransactionInput {
-                outpoint: genesis_utxo.clone(),
-                sig_script: H512::zero()
-            }],
-            outputs: vec![ TransactionOutput::new(50, bob_h256)]
+            inputs: vec![TransactionInput::new(outpoint, vec![vec![0; 65]; c as usize])],
+            outputs: vec![TransactionOutput::new(1_000_000, H256::repeat_byte(2))],
         };
+        tx.outputs[0].value -= minimum_fee::<T>(&tx);
+
+        let message = signature_message(&tx, 0, SigHash::All).unwrap();
+        tx.inputs[0].witness = keys
+            .iter()
+            .enumerate()
+            .map(|(index, key)| {
+                let signature = crypto::sr25519_sign(SR25519, key, &message).unwrap();
+                let mut item = vec![index as u8];
+                item.extend_from_slice(&signature.0);
+                item
+            })
+            .collect();
+    }: spend(RawOrigin::None, tx.clone())
+    verify {
+        assert_last_event::<T>(Event::TransactionSuccess(tx).into());
+    }
+
+    issue_token {
+        let caller: T::AccountId = whitelisted_caller();
+        let token_id = NextTokenId::<T>::get();
+    }: _(RawOrigin::Signed(caller), b"TKN".to_vec(), 18, H256::repeat_byte(1), 1_000_000, None)
+    verify {
+        assert!(TokenRegistry::<T>::contains_key(token_id));
+    }
+
+    mint {
+        let caller: T::AccountId = whitelisted_caller();
+        let issuer = crypto::sr25519_generate(SR25519, None);
+        let token_id = NextTokenId::<T>::get();
+        Utxo::<T>::issue_token(
+            RawOrigin::Signed(caller.clone()).into(),
+            b"TKN".to_vec(),
+            18,
+            H256::from(issuer),
+            1_000_000,
+            None,
+        )?;
+
+        let recipient = H256::repeat_byte(1);
+        let message = mint_message(token_id, 0, 1_000, &recipient);
+        let signature = crypto::sr25519_sign(SR25519, &issuer, &message).unwrap();
+    }: _(RawOrigin::Signed(caller), token_id, 1_000, recipient, H512(signature.0))
+    verify {
+        assert_eq!(TokenSupply::<T>::get(token_id), 1_001_000);
+    }
 
-        tx.inputs[0].sig_script = alice_sigscript;
+    issue_nft_class {
+        let caller: T::AccountId = whitelisted_caller();
+        let token_id = NextTokenId::<T>::get();
+    }: _(RawOrigin::Signed(caller), b"ART".to_vec(), H256::repeat_byte(1))
+    verify {
+        assert!(TokenRegistry::<T>::contains_key(token_id));
+    }
 
+    mint_nft {
         let caller: T::AccountId = whitelisted_caller();
-    }: spend(RawOrigin::Signed(caller), tx.clone())
+        let issuer = crypto::sr25519_generate(SR25519, None);
+        let token_id = NextTokenId::<T>::get();
+        Utxo::<T>::issue_nft_class(
+            RawOrigin::Signed(caller.clone()).into(),
+            b"ART".to_vec(),
+            H256::from(issuer),
+        )?;
+
+        let nft = NftData::new(H256::repeat_byte(2), Some(vec![b'a'; MAX_URI_LEN]));
+        let recipient = H256::repeat_byte(1);
+        let message = mint_nft_message(token_id, 0, &nft, &recipient);
+        let signature = crypto::sr25519_sign(SR25519, &issuer, &message).unwrap();
+    }: _(RawOrigin::Signed(caller), token_id, nft.clone(), recipient, H512(signature.0))
+    verify {
+        assert!(Nfts::<T>::contains_key(token_id, nft.content_hash()));
+    }
+
+    // the rewards of `t` tokens, split among `a` authorities at the end of a block
+    disperse_reward {
+        let t in 1 .. MAX_REWARD_TOKENS;
+        let a in 1 .. MAX_AUTHORITIES;
+        let auths: Vec<H256> = (0..a)
+            .map(|index| H256::from_low_u64_be(index as u64 + 1))
+            .collect();
+        for token_id in 0..t {
+            RewardTotal::<T>::insert(token_id as TokenID, 1_000_000);
+        }
+    }: {
+        disperse_reward::<T>(&auths, 1u32.into());
+    }
+    verify {
+        assert_eq!(RewardTotal::<T>::iter().count(), 0);
+    }
+
+    // a plain spend on the runtime, of a utxo of its own rather than of the chain's genesis
+    runtime_spend {
+        let tx = consolidation_tx::<T>(1);
+        let outpoint = tx.inputs[0].outpoint;
+        let reward = RewardTotal::<T>::get(TokenType::MLT as TokenID);
+        let fee = 1_000_000 - tx.outputs[0].value;
+    }: spend(RawOrigin::None, tx.clone())
     verify {
         assert_last_event::<T>(Event::TransactionSuccess(tx).into());
-        assert_eq!(RewardTotal::<T>::get(),50u128);
-        assert!(!UtxoStore::<T>::contains_key(genesis_utxo));
+        assert_eq!(RewardTotal::<T>::get(TokenType::MLT as TokenID), reward + fee);
+        assert!(!UtxoStore::<T>::contains_key(outpoint));
     }
 }
 
@@ -131,5 +401,66 @@ mod tests {
         new_test_ext().execute_with(|| {
             assert_ok!(test_benchmark_test_spend::<Test>());
         });
+        new_test_ext().execute_with(|| {
+            assert_ok!(test_benchmark_runtime_spend::<Test>());
+        });
+    }
+
+    #[test]
+    fn spend_inputs() {
+        new_test_ext().execute_with(|| {
+            assert_ok!(test_benchmark_spend_inputs::<Test>());
+        });
+        new_test_ext().execute_with(|| {
+            assert_ok!(test_benchmark_spend_inputs_batched::<Test>());
+        });
+    }
+
+    #[test]
+    fn spend_witness() {
+        new_test_ext().execute_with(|| {
+            assert_ok!(test_benchmark_spend_witness::<Test>());
+        });
+    }
+
+    #[test]
+    fn spend_zk() {
+        new_test_ext().execute_with(|| {
+            assert_ok!(test_benchmark_spend_zk::<Test>());
+        });
+    }
+
+    #[test]
+    fn spend_multisig() {
+        new_test_ext().execute_with(|| {
+            assert_ok!(test_benchmark_spend_multisig::<Test>());
+        });
+    }
+
+    #[test]
+    fn issue_token() {
+        new_test_ext().execute_with(|| {
+            assert_ok!(test_benchmark_issue_token::<Test>());
+        });
+        new_test_ext().execute_with(|| {
+            assert_ok!(test_benchmark_mint::<Test>());
+        });
+    }
+
+    #[test]
+    fn disperse_reward() {
+        new_test_ext().execute_with(|| {
+            assert_ok!(test_benchmark_disperse_reward::<Test>());
+        });
+    }
+
+    #[test]
+    fn issue_nft() {
+        new_test_ext().execute_with(|| {
+            assert_ok!(test_benchmark_issue_nft_class::<Test>());
+        });
+        new_test_ext().execute_with(|| {
+            assert_ok!(test_benchmark_mint_nft::<Test>());
+        });
     }
 }
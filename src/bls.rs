@@ -0,0 +1,109 @@
+// Copyright (c) 2021 RBB S.r.l
+// opensource@mintlayer.org
+// SPDX-License-Identifier: MIT
+// Licensed under the MIT License;
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://spdx.org/licenses/MIT
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// Author(s): C. Yap
+
+//! BLS12-381 signatures verified in the runtime itself, with keys in G1 and signatures in G2,
+//! both in the canonical compressed serialization of arkworks.
+//!
+//! A message is hashed to G2 by try-and-increment: the hash of the message and a counter is
+//! taken as the x coordinate of a point, until one is on the curve, whose cofactor is then
+//! cleared. This is not the hash to curve of the IETF draft, so signers have to use
+//! `hash_to_g2` as well.
+
+use crate::BlsVerifier;
+use ark_bls12_381::{Bls12_381, Fq, Fq2, G1Affine, G2Affine};
+use ark_ec::{AffineCurve, PairingEngine};
+use ark_ff::{One, PrimeField, Zero};
+use ark_serialize::CanonicalDeserialize;
+use frame_support::sp_io::hashing::blake2_256;
+use sp_core::sp_std::vec::Vec;
+
+/// Separates the hashes of messages to G2 from any other use of the same hash.
+const HASH_TO_G2_DOMAIN: &[u8] = b"pallet-utxo BLS12-381 G2";
+
+/// Most x coordinates `hash_to_g2` tries. About half of them are on the curve, so running out
+/// of them does not happen in practice.
+const MAX_HASH_ATTEMPTS: u8 = u8::MAX;
+
+/// The point of G2 that the key of a signer multiplies to sign `message`.
+pub fn hash_to_g2(message: &[u8]) -> Option<G2Affine> {
+    let message_hash = blake2_256(message);
+    (0..=MAX_HASH_ATTEMPTS).find_map(|counter| {
+        // each coordinate of Fq2 is reduced from 64 bytes, which leaves no noticeable bias
+        let coordinate = |part: u8| {
+            let bytes: Vec<u8> = (0..2u8)
+                .flat_map(|half| {
+                    blake2_256(&[HASH_TO_G2_DOMAIN, &message_hash, &[counter, part, half]].concat())
+                        .to_vec()
+                })
+                .collect();
+            Fq::from_le_bytes_mod_order(&bytes)
+        };
+        G2Affine::get_point_from_x(Fq2::new(coordinate(0), coordinate(1)), false)
+            .map(|point| point.mul_by_cofactor())
+            .filter(|point| !point.is_zero())
+    })
+}
+
+/// Verifies BLS signatures with the pairings of arkworks, in no_std.
+pub struct ArkworksBls;
+
+impl BlsVerifier for ArkworksBls {
+    fn verify(pub_key: &[u8], signature: &[u8], message: &[u8]) -> bool {
+        verify_pairs(&[pub_key], &[message], signature)
+    }
+
+    fn aggregate_verify(pub_keys: &[&[u8]], messages: &[&[u8]], signature: &[u8]) -> bool {
+        verify_pairs(pub_keys, messages, signature)
+    }
+}
+
+/// Whether `signature` is the sum of a signature of each of `messages` by the key at the same
+/// position in `pub_keys`: the pairing of the generator of G1 with `signature` equals the
+/// product of the pairings of each key with the hash of its message.
+fn verify_pairs(pub_keys: &[&[u8]], messages: &[&[u8]], signature: &[u8]) -> bool {
+    if pub_keys.is_empty() || pub_keys.len() != messages.len() {
+        return false;
+    }
+    // deserializing checks that the points are in the prime order subgroups
+    let signature = match G2Affine::deserialize(signature) {
+        Ok(signature) => signature,
+        Err(_) => return false,
+    };
+
+    let mut pairs: Vec<(
+        <Bls12_381 as PairingEngine>::G1Prepared,
+        <Bls12_381 as PairingEngine>::G2Prepared,
+    )> = Vec::with_capacity(pub_keys.len() + 1);
+    pairs.push((
+        (-G1Affine::prime_subgroup_generator()).into(),
+        signature.into(),
+    ));
+    for (pub_key, message) in pub_keys.iter().zip(messages) {
+        // the identity would verify any signature of its messages
+        let pub_key = match G1Affine::deserialize(*pub_key) {
+            Ok(pub_key) if !pub_key.is_zero() => pub_key,
+            _ => return false,
+        };
+        let point = match hash_to_g2(message) {
+            Some(point) => point,
+            None => return false,
+        };
+        pairs.push((pub_key.into(), point.into()));
+    }
+
+    Bls12_381::product_of_pairings(&pairs).is_one()
+}
@@ -162,8 +162,15 @@ impl OutputHeader {
         self.version.data = version as u128;
     }
 
+    /// Checks the header against the built-in `TokenType`s only.
     pub fn validate(&self) -> bool {
-        self.token_type().is_some() & self.sign_method().is_some()
+        self.validate_with(|token_id| TokenType::try_from(token_id as u128).is_ok())
+    }
+
+    /// Like `validate`, with `token_exists` telling which token ids are known, such as the
+    /// tokens issued on chain.
+    pub fn validate_with(&self, token_exists: impl FnOnce(TokenID) -> bool) -> bool {
+        token_exists(self.token_id()) & self.sign_method().is_some()
     }
 }
 
@@ -210,6 +217,7 @@ u128_to_enum! {
         BLS = 0,
         Schnorr = 1,
         ZkSnark = 2,
+        Ecdsa = 3,
     }
 }
 
@@ -264,6 +272,12 @@ mod tests {
             SignatureMethod::ZkSnark
         );
 
+        let x = 0b1010_011; // last 3 bits are 011, so signature should be Ecdsa
+        assert_eq!(
+            OutputHeader::new(x).sign_method().unwrap(),
+            SignatureMethod::Ecdsa
+        );
+
         let x = 0b10_111; // last 3 bits is are, and it's not yet supported.
         assert_eq!(OutputHeader::new(x).sign_method(), None);
 
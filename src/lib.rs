@@ -29,8 +29,15 @@ mod tests;
 #[cfg(feature = "runtime-benchmarks")]
 mod benchmarking;
 
+pub mod bls;
 mod header;
+pub mod migrations;
+#[cfg(feature = "std")]
+pub mod musig;
+pub mod script;
+pub mod taproot;
 pub mod weights;
+pub mod zk;
 
 #[frame_support::pallet]
 pub mod pallet {
@@ -38,24 +45,78 @@ pub mod pallet {
     #[cfg(feature = "std")]
     use serde::{Deserialize, Serialize};
 
-    use crate::{OutputHeader, OutputHeaderHelper, TXOutputHeader, TokenID};
-    use codec::{Decode, Encode};
+    use crate::{
+        script::{self, Script, ScriptError},
+        taproot::{self, ControlBlock},
+        zk::{self, ZkProof},
+        OutputHeader, OutputHeaderHelper, SignatureMethod, TXOutputHeader, TokenID,
+    };
+    use codec::{Decode, DecodeAll, Encode};
     use frame_support::{
         dispatch::{DispatchResultWithPostInfo, Vec},
         pallet_prelude::*,
-        sp_io::crypto,
+        sp_io::{
+            self, crypto,
+            hashing::{blake2_256, sha2_256},
+        },
         sp_runtime::traits::{BlakeTwo256, Dispatchable, Hash, SaturatedConversion},
-        traits::IsSubType,
+        storage::{unhashed, StoragePrefixedMap},
+        traits::{IsSubType, UnixTime},
     };
     use frame_system::pallet_prelude::*;
     use sp_core::{
-        sp_std::collections::btree_map::BTreeMap,
+        sp_std::{
+            collections::{btree_map::BTreeMap, btree_set::BTreeSet},
+            convert::TryFrom,
+            vec,
+        },
         sr25519::{Public as SR25Pub, Signature as SR25Sig},
         H256, H512,
     };
 
     pub type Value = u128;
 
+    /// The witness of an input: signatures and any other data needed to spend a utxo.
+    pub type Witness = Vec<Vec<u8>>;
+
+    /// Most items the witness of an input may hold.
+    pub const MAX_WITNESS_ITEMS: usize = 64;
+
+    /// Most bytes the items of an input's witness may hold altogether.
+    pub const MAX_WITNESS_SIZE: usize = 8 * 1024;
+
+    /// Most keys a multisig destination may hold, so that a key index fits in a byte.
+    pub const MAX_MULTISIG_KEYS: usize = 20;
+
+    /// Most bytes of the ticker of an issued token.
+    pub const MAX_TICKER_LEN: usize = 5;
+
+    /// Most decimals of an issued token.
+    pub const MAX_DECIMALS: u8 = 18;
+
+    /// Most bytes of the metadata URI of an NFT.
+    pub const MAX_URI_LEN: usize = 256;
+
+    /// The id of the first token issued on chain, after the built-in `TokenType`s.
+    pub const FIRST_ISSUED_TOKEN_ID: TokenID = crate::TokenType::BTC as TokenID + 1;
+
+    /// Most blocks a transaction spending a locked utxo waits in the pool. Unsigned transactions
+    /// are free to submit, so nobody may keep the pool busy with far-off locks.
+    pub const MAX_TIMELOCK_LONGEVITY: TransactionLongevity = 600;
+
+    /// Most ZkSnark inputs a transaction may spend, as each proof takes pairings to verify.
+    pub const MAX_ZK_INPUTS: usize = 10;
+
+    /// Most tokens whose rewards are paid out at the end of a block, as `on_initialize` accounts
+    /// for. MLT is always among them, and the other tokens take turns, see `RewardCursor`.
+    pub const MAX_REWARD_TOKENS: u32 = 16;
+
+    /// Size of a compressed BLS12-381 public key, a point of G1.
+    pub const BLS_PUBLIC_KEY_SIZE: usize = 48;
+
+    /// Size of a compressed BLS12-381 signature, a point of G2.
+    pub const BLS_SIGNATURE_SIZE: usize = 96;
+
     #[pallet::pallet]
     #[pallet::generate_store(pub(super) trait Store)]
     pub struct Pallet<T>(PhantomData<T>);
@@ -70,11 +131,298 @@ pub mod pallet {
 
         type WeightInfo: WeightInfo;
 
+        /// Checks the signatures of the inputs. Use `DefaultVerifier` unless the runtime
+        /// needs other signature schemes.
+        type SignatureVerifier: SignatureVerifier;
+
+        /// The chain's time, against which `Timelock::UntilTime` is checked.
+        type Time: UnixTime;
+
+        /// The MLT fee a transaction pays at least for each byte of its encoding.
+        ///
+        /// The minimum fee is only ever paid in MLT: values of other tokens have no common
+        /// measure with it. Under a non-zero policy, a transfer of other tokens alone is refused
+        /// with `InsufficientFee` and has to spend an MLT input to pay it.
+        #[pallet::constant]
+        type MinimumFeePerByte: Get<Value>;
+
+        /// The MLT fee a transaction pays at least for each of its inputs.
+        #[pallet::constant]
+        type MinimumFeePerInput: Get<Value>;
+
+        /// The MLT fee a transaction pays at least for each of its outputs.
+        #[pallet::constant]
+        type MinimumFeePerOutput: Get<Value>;
+
         fn authorities() -> Vec<H256>;
     }
 
     pub trait WeightInfo {
-        fn spend(u: u32) -> Weight;
+        fn spend(u: u32, w: u32, z: u32, c: u32) -> Weight;
+        fn issue_token() -> Weight;
+        fn mint() -> Weight;
+        fn issue_nft_class() -> Weight;
+        fn mint_nft() -> Weight;
+        fn disperse_reward(t: u32, a: u32) -> Weight;
+    }
+
+    /// Why a signature was not accepted.
+    #[derive(Clone, Copy, Eq, PartialEq, RuntimeDebug)]
+    pub enum SignatureError {
+        /// The verifier does not know the signature method of the spent utxo.
+        UnsupportedMethod,
+        /// The signature does not match the message or the public key.
+        Invalid,
+    }
+
+    /// Verifies the signature of an input against one of the keys of the utxo it spends.
+    pub trait SignatureVerifier {
+        fn verify(
+            utxo: &TransactionOutput,
+            pub_key: &H256,
+            signature: &[u8],
+            message: &[u8],
+        ) -> Result<(), SignatureError>;
+
+        /// Verifies one BLS `signature` aggregating a signature of each message by its key,
+        /// see `BlsVerifier::aggregate_verify`. Verifiers without BLS support refuse it.
+        fn verify_bls_aggregate(
+            _pub_keys: &[&[u8]],
+            _messages: &[&[u8]],
+            _signature: &[u8],
+        ) -> Result<(), SignatureError> {
+            Err(SignatureError::UnsupportedMethod)
+        }
+
+        /// Whether BLS signatures are verified at all. Without, new BLS outputs are refused,
+        /// since they could never be spent.
+        fn supports_bls() -> bool {
+            false
+        }
+    }
+
+    /// BLS12-381 signatures, with keys in G1 and signatures in G2. `bls::ArkworksBls` verifies
+    /// them in the runtime itself, and the unit type supports no BLS at all.
+    pub trait BlsVerifier {
+        /// Whether this verifier supports BLS, otherwise BLS signatures are refused as
+        /// `SignatureError::UnsupportedMethod`.
+        const SUPPORTED: bool = true;
+
+        /// Whether `signature` is a valid signature of `message` by `pub_key`.
+        fn verify(pub_key: &[u8], signature: &[u8], message: &[u8]) -> bool;
+
+        /// Whether `signature` is the aggregate of a valid signature of each of `messages`
+        /// by the key at the same position in `pub_keys`.
+        fn aggregate_verify(pub_keys: &[&[u8]], messages: &[&[u8]], signature: &[u8]) -> bool;
+    }
+
+    impl BlsVerifier for () {
+        const SUPPORTED: bool = false;
+
+        fn verify(_pub_key: &[u8], _signature: &[u8], _message: &[u8]) -> bool {
+            false
+        }
+
+        fn aggregate_verify(_pub_keys: &[&[u8]], _messages: &[&[u8]], _signature: &[u8]) -> bool {
+            false
+        }
+    }
+
+    /// Dispatches on the signature method in the header of the spent utxo.
+    /// Supports sr25519, which is Schnorr over the Ristretto group, ECDSA over secp256k1,
+    /// Groth16 proofs, see `zk`, and BLS12-381 with the `BlsVerifier` `B`, by default
+    /// `bls::ArkworksBls`.
+    ///
+    /// sr25519 signatures join the batch of the block being imported: the executive starts
+    /// batch verification before the extrinsics and fails the block if any signature of the
+    /// batch is invalid. Outside of a batch, e.g. in the transaction pool, they are verified
+    /// right away.
+    pub struct DefaultVerifier<B = crate::bls::ArkworksBls>(PhantomData<B>);
+
+    impl<B: BlsVerifier> SignatureVerifier for DefaultVerifier<B> {
+        fn verify(
+            utxo: &TransactionOutput,
+            pub_key: &H256,
+            signature: &[u8],
+            message: &[u8],
+        ) -> Result<(), SignatureError> {
+            match OutputHeader::new(utxo.header).sign_method() {
+                Some(SignatureMethod::Schnorr) => {
+                    let signature =
+                        <[u8; 64]>::try_from(signature).map_err(|_| SignatureError::Invalid)?;
+                    ensure!(
+                        crypto::sr25519_batch_verify(
+                            &SR25Sig::from_raw(signature),
+                            message,
+                            &SR25Pub::from_h256(*pub_key)
+                        ),
+                        SignatureError::Invalid
+                    );
+                    Ok(())
+                }
+                Some(SignatureMethod::Ecdsa) => {
+                    ensure!(
+                        ecdsa_recover(signature, message)
+                            .iter()
+                            .any(|key| ecdsa_key_hash(key) == *pub_key),
+                        SignatureError::Invalid
+                    );
+                    Ok(())
+                }
+                Some(SignatureMethod::BLS) => {
+                    ensure!(B::SUPPORTED, SignatureError::UnsupportedMethod);
+                    let (key, signature) = split_bls_signature(signature)
+                        .filter(|(key, _)| bls_key_hash(key) == *pub_key)
+                        .ok_or(SignatureError::Invalid)?;
+                    ensure!(B::verify(key, signature, message), SignatureError::Invalid);
+                    Ok(())
+                }
+                Some(SignatureMethod::ZkSnark) => {
+                    let zk_proof =
+                        ZkProof::decode_all(signature).map_err(|_| SignatureError::Invalid)?;
+                    ensure!(
+                        zk::verify(pub_key, &zk_proof, message),
+                        SignatureError::Invalid
+                    );
+                    Ok(())
+                }
+                _ => Err(SignatureError::UnsupportedMethod),
+            }
+        }
+
+        fn verify_bls_aggregate(
+            pub_keys: &[&[u8]],
+            messages: &[&[u8]],
+            signature: &[u8],
+        ) -> Result<(), SignatureError> {
+            ensure!(B::SUPPORTED, SignatureError::UnsupportedMethod);
+            ensure!(
+                B::aggregate_verify(pub_keys, messages, signature),
+                SignatureError::Invalid
+            );
+            Ok(())
+        }
+
+        fn supports_bls() -> bool {
+            B::SUPPORTED
+        }
+    }
+
+    /// BLS outputs are locked to the hash of the compressed key, which does not fit in a
+    /// `Destination` either. The key comes with the signature instead, see `split_bls_signature`.
+    pub fn bls_key_hash(compressed_key: &[u8]) -> H256 {
+        BlakeTwo256::hash(compressed_key)
+    }
+
+    /// Splits a BLS signature item of a witness, the compressed key followed by the signature.
+    pub fn split_bls_signature(item: &[u8]) -> Option<(&[u8], &[u8])> {
+        if item.len() == BLS_PUBLIC_KEY_SIZE + BLS_SIGNATURE_SIZE {
+            Some(item.split_at(BLS_PUBLIC_KEY_SIZE))
+        } else {
+            None
+        }
+    }
+
+    /// ECDSA outputs are locked to the hash of the 33 bytes compressed secp256k1 key,
+    /// since the key does not fit in a `Destination`.
+    pub fn ecdsa_key_hash(compressed_key: &[u8; 33]) -> H256 {
+        BlakeTwo256::hash(compressed_key)
+    }
+
+    /// The compressed keys which could have produced `signature` over the blake2 hash of
+    /// `message`. The 65 bytes form carries its recovery id; the 64 bytes `r || s` form is
+    /// tried with both recovery ids.
+    fn ecdsa_recover(signature: &[u8], message: &[u8]) -> Vec<[u8; 33]> {
+        let mut candidates: Vec<[u8; 65]> = Vec::new();
+        match signature.len() {
+            65 => {
+                let mut sig = [0u8; 65];
+                sig.copy_from_slice(signature);
+                candidates.push(sig);
+            }
+            64 => {
+                for recovery_id in 0..2 {
+                    let mut sig = [0u8; 65];
+                    sig[..64].copy_from_slice(signature);
+                    sig[64] = recovery_id;
+                    candidates.push(sig);
+                }
+            }
+            _ => {}
+        }
+
+        let message = blake2_256(message);
+        candidates
+            .iter()
+            .filter_map(|sig| crypto::secp256k1_ecdsa_recover(sig, &message).ok())
+            .map(|uncompressed| {
+                // the x coordinate, prefixed by the parity of the y coordinate
+                let mut compressed = [0u8; 33];
+                compressed[0] = 2 + (uncompressed[63] & 1);
+                compressed[1..].copy_from_slice(&uncompressed[..32]);
+                compressed
+            })
+            .collect()
+    }
+
+    /// Points to the output at `index` of the transaction identified by `txid`.
+    /// Genesis utxos and block rewards use the same rule, see `GenesisConfig` and `disperse_reward`.
+    #[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+    #[derive(
+        Clone, Copy, Encode, Decode, Eq, PartialEq, PartialOrd, Ord, RuntimeDebug, Hash, Default,
+    )]
+    pub struct OutPoint {
+        pub(crate) txid: H256,
+        pub(crate) index: u64,
+    }
+
+    impl OutPoint {
+        pub fn new(txid: H256, index: u64) -> Self {
+            Self { txid, index }
+        }
+
+        pub fn txid(&self) -> H256 {
+            self.txid
+        }
+
+        pub fn index(&self) -> u64 {
+            self.index
+        }
+    }
+
+    /// Which parts of the transaction an input's signature commits to.
+    /// Every mode commits to the input itself and to its sighash.
+    #[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+    #[derive(Clone, Copy, Encode, Decode, Eq, PartialEq, PartialOrd, Ord, RuntimeDebug, Hash)]
+    pub enum SigHash {
+        /// All inputs and all outputs.
+        All,
+        /// All inputs and no outputs.
+        None,
+        /// All inputs and the output with the same index as the input.
+        Single,
+        /// Only this input, and all outputs.
+        AllAnyoneCanPay,
+        /// Only this input, and no outputs.
+        NoneAnyoneCanPay,
+        /// Only this input, and the output with the same index as the input, at that index.
+        SingleAnyoneCanPay,
+    }
+
+    impl Default for SigHash {
+        fn default() -> Self {
+            SigHash::All
+        }
+    }
+
+    impl SigHash {
+        /// Whether other parties can add inputs after this one is signed.
+        pub fn anyone_can_pay(&self) -> bool {
+            matches!(
+                self,
+                SigHash::AllAnyoneCanPay | SigHash::NoneAnyoneCanPay | SigHash::SingleAnyoneCanPay
+            )
+        }
     }
 
     #[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
@@ -82,17 +430,142 @@ pub mod pallet {
         Clone, Encode, Decode, Eq, PartialEq, PartialOrd, Ord, RuntimeDebug, Hash, Default,
     )]
     pub struct TransactionInput {
-        pub(crate) outpoint: H256,
-        pub(crate) sig_script: H512,
+        pub(crate) outpoint: OutPoint,
+        pub(crate) witness: Witness,
+        pub(crate) sighash: SigHash,
     }
 
     impl TransactionInput {
-        pub fn new(outpoint: H256, sig_script: H512) -> Self {
+        /// The signature of the input commits to the whole transaction, see `SigHash::All`.
+        pub fn new(outpoint: OutPoint, witness: Witness) -> Self {
+            Self::new_with_sighash(outpoint, witness, SigHash::All)
+        }
+
+        pub fn new_with_sighash(outpoint: OutPoint, witness: Witness, sighash: SigHash) -> Self {
             Self {
                 outpoint,
-                sig_script,
+                witness,
+                sighash,
             }
         }
+
+        /// Number of bytes held by the witness items.
+        pub fn witness_size(&self) -> usize {
+            self.witness.iter().map(Vec::len).sum()
+        }
+    }
+
+    /// What it takes to spend an output. The keys use the signature method of the output header.
+    #[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+    #[derive(Clone, Encode, Decode, Eq, PartialEq, PartialOrd, Ord, RuntimeDebug, Hash)]
+    pub enum Destination {
+        /// A signature of the key.
+        Pubkey(H256),
+        /// Signatures of at least `threshold` of the keys. Each witness item is the index of
+        /// the signing key in `keys`, as a single byte, followed by its signature.
+        Multisig { threshold: u32, keys: Vec<H256> },
+        /// A hash-time-locked contract. The `recipient` claims it with a signature followed by
+        /// the preimage of the sha256 `hash`, the same hash a Bitcoin HTLC commits to. From the
+        /// block `timeout` on, the `refund` key can also take it back with a signature alone.
+        Htlc {
+            recipient: H256,
+            refund: H256,
+            hash: H256,
+            timeout: u64,
+        },
+        /// Whatever the script accepts, with the witness as its initial stack, see `script`.
+        Script(Script),
+        /// Commits to another destination, the policy, by its `script_hash`. The last witness
+        /// item reveals the encoded policy and the other items satisfy it, see `is_policy`.
+        ScriptHash(H256),
+        /// An sr25519 key tweaked to commit to a Merkle tree of policies, see `taproot`. A single
+        /// signature of the key spends it. Otherwise the last two witness items reveal the
+        /// encoded policy and its `ControlBlock`, and the other items satisfy the policy.
+        Taproot(H256),
+        /// Nobody: the value is burnt, and the output is not even stored.
+        Burn,
+    }
+
+    impl Default for Destination {
+        fn default() -> Self {
+            Destination::Pubkey(H256::zero())
+        }
+    }
+
+    impl Destination {
+        /// Whether the destination can be revealed by a script hash or a taproot script path.
+        /// Those cannot nest, and revealing a burn would be pointless.
+        pub fn is_policy(&self) -> bool {
+            self.is_valid()
+                && !matches!(
+                    self,
+                    Destination::ScriptHash(_) | Destination::Taproot(_) | Destination::Burn
+                )
+        }
+
+        /// The hash a `Destination::ScriptHash` commits to this policy by.
+        pub fn script_hash(&self) -> H256 {
+            BlakeTwo256::hash_of(self)
+        }
+
+        /// Whether the destination can be spent at all, or burns on purpose: a multisig needs a
+        /// reachable, non-zero threshold of distinct keys.
+        pub fn is_valid(&self) -> bool {
+            match self {
+                Destination::Pubkey(_) | Destination::Htlc { .. } | Destination::Burn => true,
+                Destination::Script(script) => script::is_valid(script),
+                Destination::ScriptHash(_) | Destination::Taproot(_) => true,
+                Destination::Multisig { threshold, keys } => {
+                    let distinct_keys: BTreeSet<_> = keys.iter().collect();
+                    *threshold > 0
+                        && *threshold as usize <= keys.len()
+                        && keys.len() <= MAX_MULTISIG_KEYS
+                        && distinct_keys.len() == keys.len()
+                }
+            }
+        }
+    }
+
+    /// Until when an output cannot be spent.
+    #[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+    #[derive(Clone, Copy, Encode, Decode, Eq, PartialEq, PartialOrd, Ord, RuntimeDebug, Hash)]
+    pub enum Timelock {
+        /// Spendable in blocks from this number on.
+        UntilBlock(u64),
+        /// Spendable once the chain's time, in seconds since the unix epoch, reaches this.
+        UntilTime(u64),
+        /// Spendable this many blocks after the block which created the output.
+        AfterBlocks(u64),
+    }
+
+    /// What makes an output of an NFT class unique: the hash of its content, and where to find
+    /// metadata about it. Neither changes as the NFT is transferred.
+    #[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+    #[derive(Clone, Encode, Decode, Eq, PartialEq, PartialOrd, Ord, RuntimeDebug, Hash)]
+    pub struct NftData {
+        pub(crate) content_hash: H256,
+        pub(crate) uri: Option<Vec<u8>>,
+    }
+
+    impl NftData {
+        pub fn new(content_hash: H256, uri: Option<Vec<u8>>) -> Self {
+            Self { content_hash, uri }
+        }
+
+        pub fn content_hash(&self) -> H256 {
+            self.content_hash
+        }
+
+        pub fn uri(&self) -> Option<&[u8]> {
+            self.uri.as_deref()
+        }
+
+        /// Whether the URI, if any, is neither empty nor longer than `MAX_URI_LEN`.
+        pub fn is_valid(&self) -> bool {
+            self.uri
+                .as_ref()
+                .map_or(true, |uri| !uri.is_empty() && uri.len() <= MAX_URI_LEN)
+        }
     }
 
     #[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
@@ -101,20 +574,191 @@ pub mod pallet {
     )]
     pub struct TransactionOutput {
         pub(crate) value: Value,
-        pub(crate) pub_key: H256,
+        pub(crate) destination: Destination,
         pub(crate) header: TXOutputHeader,
+        pub(crate) timelock: Option<Timelock>,
+        /// Set on the outputs of NFT classes only, whose value is always 1.
+        pub(crate) nft: Option<NftData>,
     }
 
     impl TransactionOutput {
-        /// By default the header is 0:
+        /// By default the header is 1:
         /// token type for both the value and fee is MLT,
-        /// and the signature method is BLS.
+        /// and the signature method is Schnorr, as `pub_key` is an sr25519 key.
         /// functions are available in TXOutputHeaderImpls to update the header.
         pub fn new(value: Value, pub_key: H256) -> Self {
+            let mut header = OutputHeader::new(0);
+            header.set_sign_method(SignatureMethod::Schnorr);
+
+            Self {
+                value,
+                destination: Destination::Pubkey(pub_key),
+                header: header.as_u128(),
+                timelock: None,
+                nft: None,
+            }
+        }
+
+        /// An output locked to a secp256k1 key, see `ecdsa_key_hash`.
+        pub fn new_ecdsa(value: Value, compressed_key: &[u8; 33]) -> Self {
+            let mut header = OutputHeader::new(0);
+            header.set_sign_method(SignatureMethod::Ecdsa);
+
+            Self {
+                value,
+                destination: Destination::Pubkey(ecdsa_key_hash(compressed_key)),
+                header: header.as_u128(),
+                timelock: None,
+                nft: None,
+            }
+        }
+
+        /// An output locked to a BLS12-381 key, see `bls_key_hash`.
+        pub fn new_bls(value: Value, compressed_key: &[u8; BLS_PUBLIC_KEY_SIZE]) -> Self {
+            let mut header = OutputHeader::new(0);
+            header.set_sign_method(SignatureMethod::BLS);
+
+            Self {
+                value,
+                destination: Destination::Pubkey(bls_key_hash(compressed_key)),
+                header: header.as_u128(),
+                timelock: None,
+                nft: None,
+            }
+        }
+
+        /// An output locked to a Groth16 statement: proofs under `verifying_key` of the
+        /// `public_inputs`, see `zk::statement_hash`.
+        pub fn new_zk(value: Value, verifying_key: &[u8], public_inputs: &[Vec<u8>]) -> Self {
+            let mut header = OutputHeader::new(0);
+            header.set_sign_method(SignatureMethod::ZkSnark);
+
+            Self {
+                value,
+                destination: Destination::Pubkey(zk::statement_hash(verifying_key, public_inputs)),
+                header: header.as_u128(),
+                timelock: None,
+                nft: None,
+            }
+        }
+
+        /// An output burning `value`, see `Destination::Burn`.
+        pub fn new_burn(value: Value) -> Self {
+            Self {
+                destination: Destination::Burn,
+                ..Self::new(value, H256::zero())
+            }
+        }
+
+        /// An output locked to `threshold` signatures of the sr25519 `keys`.
+        pub fn new_multisig(value: Value, threshold: u32, keys: Vec<H256>) -> Self {
+            let mut header = OutputHeader::new(0);
+            header.set_sign_method(SignatureMethod::Schnorr);
+
             Self {
                 value,
-                pub_key,
-                header: 0,
+                destination: Destination::Multisig { threshold, keys },
+                header: header.as_u128(),
+                timelock: None,
+                nft: None,
+            }
+        }
+
+        /// An htlc output of sr25519 keys, see `Destination::Htlc`.
+        pub fn new_htlc(
+            value: Value,
+            recipient: H256,
+            refund: H256,
+            hash: H256,
+            timeout: u64,
+        ) -> Self {
+            let mut header = OutputHeader::new(0);
+            header.set_sign_method(SignatureMethod::Schnorr);
+
+            Self {
+                value,
+                destination: Destination::Htlc {
+                    recipient,
+                    refund,
+                    hash,
+                    timeout,
+                },
+                header: header.as_u128(),
+                timelock: None,
+                nft: None,
+            }
+        }
+
+        /// An output locked to `script`, whose keys are sr25519 keys.
+        pub fn new_script(value: Value, script: Script) -> Self {
+            let mut header = OutputHeader::new(0);
+            header.set_sign_method(SignatureMethod::Schnorr);
+
+            Self {
+                value,
+                destination: Destination::Script(script),
+                header: header.as_u128(),
+                timelock: None,
+                nft: None,
+            }
+        }
+
+        /// An output committing to `policy`, whose keys are sr25519 keys.
+        pub fn new_script_hash(value: Value, policy: &Destination) -> Self {
+            let mut header = OutputHeader::new(0);
+            header.set_sign_method(SignatureMethod::Schnorr);
+
+            Self {
+                value,
+                destination: Destination::ScriptHash(policy.script_hash()),
+                header: header.as_u128(),
+                timelock: None,
+                nft: None,
+            }
+        }
+
+        /// An output locked to the tweak of the sr25519 `internal_key` by the Merkle root of its
+        /// policies, see `taproot`. `None` if `internal_key` is not a valid key.
+        pub fn new_taproot(
+            value: Value,
+            internal_key: &H256,
+            merkle_root: Option<H256>,
+        ) -> Option<Self> {
+            let mut header = OutputHeader::new(0);
+            header.set_sign_method(SignatureMethod::Schnorr);
+
+            Some(Self {
+                value,
+                destination: Destination::Taproot(taproot::tweaked_key(internal_key, merkle_root)?),
+                header: header.as_u128(),
+                timelock: None,
+                nft: None,
+            })
+        }
+
+        /// The same output, which cannot be spent before `timelock` expires.
+        pub fn with_timelock(self, timelock: Timelock) -> Self {
+            Self {
+                timelock: Some(timelock),
+                ..self
+            }
+        }
+
+        /// The same output, holding `token_id` rather than MLT.
+        pub fn with_token_id(self, token_id: TokenID) -> Self {
+            let mut header = OutputHeader::new(self.header);
+            header.set_token_id(token_id);
+            Self {
+                header: header.as_u128(),
+                ..self
+            }
+        }
+
+        /// The same output, holding the NFT `nft` of its token's class.
+        pub fn with_nft(self, nft: NftData) -> Self {
+            Self {
+                nft: Some(nft),
+                ..self
             }
         }
     }
@@ -126,81 +770,767 @@ pub mod pallet {
         pub(crate) outputs: Vec<TransactionOutput>,
     }
 
+    impl Transaction {
+        /// Number of inputs whose witness is a single `ZkProof`, which covers every input
+        /// spending a ZkSnark output.
+        pub fn zk_proofs(&self) -> usize {
+            self.inputs
+                .iter()
+                .filter(|input| match input.witness.as_slice() {
+                    [item] => ZkProof::decode_all(item).is_ok(),
+                    _ => false,
+                })
+                .count()
+        }
+
+        /// Number of inputs whose witness takes pairings to check: a single `ZkProof`, or a
+        /// single BLS key, with or without a signature. Key sized items of other inputs are
+        /// counted as well, which errs on the safe side.
+        pub fn pairing_checks(&self) -> usize {
+            let bls_items = self
+                .inputs
+                .iter()
+                .filter(|input| match input.witness.as_slice() {
+                    [item] => {
+                        item.len() == BLS_PUBLIC_KEY_SIZE
+                            || item.len() == BLS_PUBLIC_KEY_SIZE + BLS_SIGNATURE_SIZE
+                    }
+                    _ => false,
+                })
+                .count();
+            self.zk_proofs().saturating_add(bls_items)
+        }
+
+        /// Number of bytes held by the witnesses of all inputs.
+        pub fn witness_size(&self) -> usize {
+            self.inputs.iter().map(TransactionInput::witness_size).sum()
+        }
+
+        /// Number of items held by the witnesses of all inputs. A multisig input checks one
+        /// signature per item, and a script no more than its witness holds, so this bounds the
+        /// signatures checked by the transaction.
+        pub fn witness_items(&self) -> usize {
+            self.inputs.iter().map(|input| input.witness.len()).sum()
+        }
+
+        /// Hash of the transaction without its signatures.
+        /// Re-encoding a signature does not change it, so it is used to key the new outputs.
+        pub fn txid(&self) -> H256 {
+            BlakeTwo256::hash(&get_simple_transaction(self))
+        }
+
+        /// Hash of the whole transaction, signatures included.
+        pub fn wtxid(&self) -> H256 {
+            BlakeTwo256::hash_of(self)
+        }
+
+        /// The outpoint of the output at `index`.
+        pub fn outpoint(&self, index: u64) -> OutPoint {
+            OutPoint::new(self.txid(), index)
+        }
+    }
+
+    /// Layout of the pallet's storage, used to run each migration only once.
+    #[derive(Encode, Decode, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, RuntimeDebug)]
+    pub enum Releases {
+        /// Utxos are keyed by opaque hashes.
+        V1,
+        /// Utxos are keyed by outpoints.
+        V2,
+        /// Utxos created by `TransactionOutput::new` claim the Schnorr signature method.
+        V3,
+        /// Utxos are locked to a `Destination` rather than a bare key.
+        V4,
+        /// Utxos may carry a `Timelock`.
+        V5,
+        /// The creation block of each utxo is recorded in `UtxoCreation`.
+        V6,
+        /// Utxos may carry `NftData`.
+        V7,
+        /// Rewards are collected per token in `RewardTotal`.
+        V8,
+    }
+
+    impl Default for Releases {
+        fn default() -> Self {
+            Releases::V1
+        }
+    }
+
+    /// Whether the outputs of a token are interchangeable amounts, or unique assets.
+    #[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+    #[derive(Clone, Copy, Encode, Decode, Eq, PartialEq, RuntimeDebug)]
+    pub enum TokenKind {
+        /// Issued with `issue_token`.
+        Fungible,
+        /// An NFT class, issued with `issue_nft_class`. Each output holds one NFT, which is only
+        /// ever transferred whole or burnt.
+        NonFungible,
+    }
+
+    /// A token issued on chain by `issue_token` or `issue_nft_class`.
+    #[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+    #[derive(Clone, Encode, Decode, Eq, PartialEq, RuntimeDebug)]
+    pub struct TokenInfo {
+        pub(crate) ticker: Vec<u8>,
+        pub(crate) decimals: u8,
+        pub(crate) issuer: H256,
+        pub(crate) initial_supply: Value,
+        pub(crate) max_supply: Option<Value>,
+        pub(crate) mints: u64,
+        pub(crate) kind: TokenKind,
+    }
+
+    impl TokenInfo {
+        pub fn ticker(&self) -> &[u8] {
+            &self.ticker
+        }
+
+        pub fn decimals(&self) -> u8 {
+            self.decimals
+        }
+
+        /// The sr25519 key the initial supply was locked to.
+        pub fn issuer(&self) -> H256 {
+            self.issuer
+        }
+
+        pub fn initial_supply(&self) -> Value {
+            self.initial_supply
+        }
+
+        /// The cap of the circulating supply, if any.
+        pub fn max_supply(&self) -> Option<Value> {
+            self.max_supply
+        }
+
+        /// How many times the issuer minted more supply, which the next `mint_message` commits
+        /// to so that a signature cannot be replayed.
+        pub fn mints(&self) -> u64 {
+            self.mints
+        }
+
+        pub fn kind(&self) -> TokenKind {
+            self.kind
+        }
+    }
+
+    /// The message the issuer of `token_id` signs to mint `amount` to `recipient`, with the
+    /// current `TokenInfo::mints`.
+    pub fn mint_message(token_id: TokenID, mints: u64, amount: Value, recipient: &H256) -> Vec<u8> {
+        (b"mint", token_id, mints, amount, recipient).encode()
+    }
+
+    /// The message the issuer of the NFT class `token_id` signs to mint `nft` to `recipient`,
+    /// with the current `TokenInfo::mints`.
+    pub fn mint_nft_message(
+        token_id: TokenID,
+        mints: u64,
+        nft: &NftData,
+        recipient: &H256,
+    ) -> Vec<u8> {
+        (b"mint_nft", token_id, mints, nft, recipient).encode()
+    }
+
+    /// Whether `ticker` is made of 1 to `MAX_TICKER_LEN` uppercase letters and digits.
+    pub fn is_valid_ticker(ticker: &[u8]) -> bool {
+        !ticker.is_empty()
+            && ticker.len() <= MAX_TICKER_LEN
+            && ticker
+                .iter()
+                .all(|byte| byte.is_ascii_uppercase() || byte.is_ascii_digit())
+    }
+
+    #[pallet::storage]
+    pub(super) type StorageVersion<T> = StorageValue<_, Releases, ValueQuery>;
+
+    /// The fees collected in each token, not yet dispersed to the block authors.
     #[pallet::storage]
     #[pallet::getter(fn reward_total)]
-    pub(super) type RewardTotal<T> = StorageValue<_, Value, ValueQuery>;
+    pub(super) type RewardTotal<T> = StorageMap<_, Blake2_128Concat, TokenID, Value, ValueQuery>;
+
+    /// The last token other than MLT whose reward was paid out. The next block pays out the
+    /// tokens after it in `RewardTotal`, so that every token is paid out in turn.
+    #[pallet::storage]
+    pub(super) type RewardCursor<T> = StorageValue<_, TokenID, OptionQuery>;
 
     #[pallet::storage]
     #[pallet::getter(fn utxo_store)]
     pub(super) type UtxoStore<T: Config> =
-        StorageMap<_, Blake2_256, H256, Option<TransactionOutput>, ValueQuery>;
+        StorageMap<_, Blake2_128Concat, OutPoint, Option<TransactionOutput>, ValueQuery>;
+
+    /// The block in which each utxo of `UtxoStore` was created.
+    #[pallet::storage]
+    #[pallet::getter(fn utxo_creation)]
+    pub(super) type UtxoCreation<T: Config> =
+        StorageMap<_, Blake2_128Concat, OutPoint, T::BlockNumber, OptionQuery>;
+
+    /// The tokens issued on chain, besides the built-in `TokenType`s.
+    #[pallet::storage]
+    #[pallet::getter(fn token_registry)]
+    pub(super) type TokenRegistry<T: Config> =
+        StorageMap<_, Blake2_128Concat, TokenID, TokenInfo, OptionQuery>;
+
+    /// The circulating supply of each token of `TokenRegistry`: what was issued and minted,
+    /// less what was burnt.
+    #[pallet::storage]
+    #[pallet::getter(fn token_supply)]
+    pub(super) type TokenSupply<T: Config> =
+        StorageMap<_, Blake2_128Concat, TokenID, Value, ValueQuery>;
+
+    /// Where each live NFT is, by class and content hash, which makes it unique in its class.
+    /// A burnt NFT is forgotten.
+    #[pallet::storage]
+    #[pallet::getter(fn nft_outpoint)]
+    pub(super) type Nfts<T: Config> = StorageDoubleMap<
+        _,
+        Blake2_128Concat,
+        TokenID,
+        Blake2_128Concat,
+        H256,
+        OutPoint,
+        OptionQuery,
+    >;
+
+    #[pallet::type_value]
+    pub(super) fn FirstIssuedTokenId() -> TokenID {
+        FIRST_ISSUED_TOKEN_ID
+    }
+
+    /// The id of the next token to be issued.
+    #[pallet::storage]
+    #[pallet::getter(fn next_token_id)]
+    pub(super) type NextTokenId<T> = StorageValue<_, TokenID, ValueQuery, FirstIssuedTokenId>;
 
     #[pallet::event]
     #[pallet::generate_deposit(pub(super) fn deposit_event)]
     pub enum Event<T: Config> {
         TransactionSuccess(Transaction),
+        /// A token was issued, and its initial supply minted at the outpoint.
+        TokenIssued(TokenID, OutPoint),
+        /// More supply of a token was minted at the outpoint.
+        TokenMinted(TokenID, OutPoint),
+        /// Some of a token was burnt.
+        TokenBurned(TokenID, Value),
+        /// An NFT class was issued.
+        NftClassIssued(TokenID),
+        /// The NFT of the class with the content hash was minted at the outpoint.
+        NftMinted(TokenID, H256, OutPoint),
+        /// The NFT of the class with the content hash was transferred to the outpoint.
+        NftTransferred(TokenID, H256, OutPoint),
+        /// The NFT of the class with the content hash was burnt.
+        NftBurned(TokenID, H256),
+        /// The utxo of the key, stored under a hash from before outpoints, was moved to the
+        /// outpoint by the storage migration.
+        LegacyUtxoMigrated(H256, OutPoint),
+    }
+
+    #[pallet::error]
+    pub enum Error<T> {
+        /// The transaction has no inputs.
+        NoInputs,
+        /// The transaction has no outputs.
+        NoOutputs,
+        /// The same input is spent more than once.
+        DuplicateInput,
+        /// The same output is defined more than once.
+        DuplicateOutput,
+        /// An input signature does not match the spent utxo's public key.
+        SignatureFailure,
+        /// An output has a value of zero, which would burn it forever.
+        ZeroOutputValue,
+        /// Too many outputs to index.
+        OutputIndexOverflow,
+        /// An output with the same hash is already in the utxo store.
+        OutputAlreadyExists,
+        /// The output header has an unsupported signature method or token id.
+        InvalidHeader,
+        /// The sum of the input values overflows.
+        InputValueOverflow,
+        /// The sum of the output values overflows.
+        OutputValueOverflow,
+        /// The outputs spend more of a token than the inputs provide.
+        OutputExceedsInput,
+        /// An output uses a token which none of the inputs provide.
+        TokenInputNotFound,
+        /// The reward computation underflows.
+        RewardUnderflow,
+        /// The reward total overflows.
+        RewardOverflow,
+        /// Some of the inputs are not in the utxo store.
+        MissingInputs,
+        /// An input signs a single output, but there is no output at the same index.
+        SigHashSingleWithoutOutput,
+        /// The spent utxo uses a signature method which cannot be verified yet.
+        UnsupportedSignatureMethod,
+        /// An input's witness has too many items or bytes.
+        WitnessTooLarge,
+        /// An input's witness does not have the shape the spent utxo requires.
+        InvalidWitness,
+        /// A multisig input is signed by fewer keys than its threshold.
+        ThresholdNotMet,
+        /// A multisig input carries two signatures of the same key.
+        DuplicateSigner,
+        /// An output's destination can never be spent, e.g. a multisig with a zero threshold.
+        InvalidDestination,
+        /// An input spends a utxo whose timelock has not expired yet.
+        TimelockNotExpired,
+        /// The preimage revealed to claim an htlc does not match its hash.
+        InvalidPreimage,
+        /// The witness of an input does not satisfy the script of the spent utxo.
+        ScriptFailed,
+        /// The policy revealed to spend a script hash does not match the hash, or is not a
+        /// valid destination.
+        InvalidPolicy,
+        /// A token ticker is empty, too long, or not made of uppercase letters and digits.
+        InvalidTicker,
+        /// A token has more than `MAX_DECIMALS` decimals.
+        TooManyDecimals,
+        /// A token is issued without any supply.
+        ZeroSupply,
+        /// No token id is left to issue.
+        TokenIdOverflow,
+        /// No token was issued with this id.
+        TokenNotFound,
+        /// Minting would take the supply of a token over its cap.
+        SupplyCapExceeded,
+        /// An output of an NFT class does not hold exactly one valid NFT, or an output of
+        /// another token holds one.
+        InvalidNft,
+        /// The NFTs of the outputs are not those of the inputs.
+        NftNotTransferred,
+        /// The class already has an NFT of this content hash.
+        NftAlreadyExists,
+        /// The token is fungible where an NFT class is expected, or the other way around.
+        WrongTokenKind,
+        /// The transaction pays less MLT than its `minimum_fee`.
+        InsufficientFee,
+        /// The transaction spends more than `MAX_ZK_INPUTS` ZkSnark outputs.
+        TooManyZkInputs,
+    }
+
+    /// Every error has its own code, so the transaction pool can tell them apart.
+    impl<T: Config> From<Error<T>> for InvalidTransaction {
+        fn from(err: Error<T>) -> Self {
+            InvalidTransaction::Custom(err.as_u8())
+        }
     }
 
     #[pallet::hooks]
     impl<T: Config> Hooks<BlockNumberFor<T>> for Pallet<T> {
+        // the rewards are paid out in `on_finalize`, at most `MAX_REWARD_TOKENS` of them
+        fn on_initialize(_block_num: T::BlockNumber) -> Weight {
+            T::WeightInfo::disperse_reward(MAX_REWARD_TOKENS, T::authorities().len() as u32)
+        }
+
         fn on_finalize(block_num: T::BlockNumber) {
             disperse_reward::<T>(&T::authorities(), block_num)
         }
+
+        fn on_runtime_upgrade() -> Weight {
+            crate::migrations::migrate::<T>()
+        }
     }
 
-    // Strips a transaction of its Signature fields by replacing value with ZERO-initialized fixed hash.
+    // Strips a transaction of its Signature fields by emptying the witnesses.
     pub fn get_simple_transaction(tx: &Transaction) -> Vec<u8> {
         let mut trx = tx.clone();
         for input in trx.inputs.iter_mut() {
-            input.sig_script = H512::zero();
+            input.witness.clear();
         }
 
         trx.encode()
     }
 
-    fn disperse_reward<T: Config>(auths: &[H256], block_number: T::BlockNumber) {
-        let reward = <RewardTotal<T>>::take();
-        let share_value: Value = reward
-            .checked_div(auths.len() as Value)
-            .ok_or("No authorities")
-            .unwrap();
-        if share_value == 0 {
-            //put reward back if it can't be split nicely
-            <RewardTotal<T>>::put(reward as Value);
-            return;
+    /// The message signed by the input at `index` of `tx`, according to `sighash`.
+    /// Returns `None` if there is no such input, or if `sighash` selects a single output
+    /// but there is no output at `index`.
+    pub fn signature_message(tx: &Transaction, index: usize, sighash: SigHash) -> Option<Vec<u8>> {
+        let input = tx.inputs.get(index)?;
+
+        let inputs: Vec<(&OutPoint, &SigHash)> = if sighash.anyone_can_pay() {
+            vec![]
+        } else {
+            tx.inputs
+                .iter()
+                .map(|input| (&input.outpoint, &input.sighash))
+                .collect()
+        };
+
+        // `Single` fixes the position of the input through the list of inputs, which
+        // `SingleAnyoneCanPay` leaves out, so the signed index is part of the message instead
+        let (outputs, output_index): (Vec<&TransactionOutput>, Option<u64>) = match sighash {
+            SigHash::All | SigHash::AllAnyoneCanPay => (tx.outputs.iter().collect(), None),
+            SigHash::None | SigHash::NoneAnyoneCanPay => (vec![], None),
+            SigHash::Single | SigHash::SingleAnyoneCanPay => {
+                (vec![tx.outputs.get(index)?], Some(index as u64))
+            }
+        };
+
+        Some((sighash, &input.outpoint, inputs, outputs, output_index).encode())
+    }
+
+    /// Checks `signature` with the runtime's `SignatureVerifier`.
+    fn verify_signature<T: Config>(
+        utxo: &TransactionOutput,
+        pub_key: &H256,
+        signature: &[u8],
+        message: &[u8],
+    ) -> Result<(), Error<T>> {
+        T::SignatureVerifier::verify(utxo, pub_key, signature, message).map_err(signature_error)
+    }
+
+    fn signature_error<T: Config>(err: SignatureError) -> Error<T> {
+        match err {
+            SignatureError::UnsupportedMethod => Error::<T>::UnsupportedSignatureMethod,
+            SignatureError::Invalid => Error::<T>::SignatureFailure,
         }
+    }
 
-        let remainder = reward
-            .checked_sub(share_value * auths.len() as Value)
-            .ok_or("Sub underflow")
-            .unwrap();
+    /// The BLS inputs of a transaction which are signed together, by one signature aggregating
+    /// the signature of each input's message. Such an input spends a BLS utxo locked to a single
+    /// key, and its witness is the compressed key alone, except for one input whose key is
+    /// followed by the aggregate signature. A transaction with any such input signs all of its
+    /// BLS single key inputs that way.
+    ///
+    /// The messages commit to the spent outpoints, so they are distinct, as aggregation requires.
+    #[derive(Default)]
+    struct BlsAggregate {
+        pub_keys: Vec<Vec<u8>>,
+        messages: Vec<Vec<u8>>,
+        signature: Option<Vec<u8>>,
+    }
 
-        log::debug!("disperse_reward:: reward total: {:?}", remainder);
-        <RewardTotal<T>>::put(remainder as Value);
+    impl BlsAggregate {
+        /// The key hash and witness item of an input spending `utxo` with `witness`, if it
+        /// can take part in an aggregate.
+        fn item<'a>(
+            utxo: &'a TransactionOutput,
+            witness: &'a [Vec<u8>],
+        ) -> Option<(&'a H256, &'a [u8])> {
+            match (&utxo.destination, witness) {
+                (Destination::Pubkey(pub_key), [item])
+                    if OutputHeader::new(utxo.header).sign_method()
+                        == Some(SignatureMethod::BLS) =>
+                {
+                    Some((pub_key, item.as_slice()))
+                }
+                _ => None,
+            }
+        }
 
-        for authority in auths {
-            // TODO: where do we get the header info?
-            // TODO: are the rewards always of MLT token type?
-            let utxo = TransactionOutput::new(share_value, *authority);
+        /// Whether `input` spends a BLS utxo locked to a single key with only that key. The
+        /// witness alone does not tell, as other inputs may hold a key sized item as well.
+        fn is_key_only<T: Config>(input: &TransactionInput) -> bool {
+            <UtxoStore<T>>::get(&input.outpoint).map_or(false, |utxo| {
+                matches!(
+                    Self::item(&utxo, &input.witness),
+                    Some((_, item)) if item.len() == BLS_PUBLIC_KEY_SIZE
+                )
+            })
+        }
 
-            let hash = {
-                let b_num = block_number.saturated_into::<u64>();
-                BlakeTwo256::hash_of(&(&utxo, b_num))
+        fn add<T: Config>(
+            &mut self,
+            pub_key: &H256,
+            item: &[u8],
+            message: Vec<u8>,
+        ) -> Result<(), Error<T>> {
+            let key = match split_bls_signature(item) {
+                Some((key, signature)) => {
+                    ensure!(self.signature.is_none(), Error::<T>::InvalidWitness);
+                    self.signature = Some(signature.to_vec());
+                    key
+                }
+                None if item.len() == BLS_PUBLIC_KEY_SIZE => item,
+                None => return Err(Error::<T>::InvalidWitness),
             };
+            ensure!(bls_key_hash(key) == *pub_key, Error::<T>::SignatureFailure);
 
-            if !<UtxoStore<T>>::contains_key(hash) {
-                <UtxoStore<T>>::insert(hash, Some(utxo));
+            self.pub_keys.push(key.to_vec());
+            self.messages.push(message);
+            Ok(())
+        }
+
+        fn verify<T: Config>(&self) -> Result<(), Error<T>> {
+            if self.pub_keys.is_empty() {
+                return Ok(());
             }
+            let signature = self.signature.as_ref().ok_or(Error::<T>::InvalidWitness)?;
+            let pub_keys: Vec<&[u8]> = self.pub_keys.iter().map(Vec::as_slice).collect();
+            let messages: Vec<&[u8]> = self.messages.iter().map(Vec::as_slice).collect();
+            T::SignatureVerifier::verify_bls_aggregate(&pub_keys, &messages, signature)
+                .map_err(signature_error)
+        }
+    }
+
+    /// How many more blocks `timelock` holds the utxo at `outpoint` at the current block, if any,
+    /// up to `MAX_TIMELOCK_LONGEVITY`. Time locks are not counted in blocks, see `is_time_locked`.
+    fn timelock_remaining<T: Config>(
+        timelock: &Timelock,
+        outpoint: &OutPoint,
+    ) -> Option<TransactionLongevity> {
+        let current_block: u64 = <frame_system::Pallet<T>>::block_number().saturated_into();
+        let blocks_until = |block: u64| {
+            block
+                .checked_sub(current_block)
+                .filter(|blocks| *blocks > 0)
+        };
+
+        let remaining = match *timelock {
+            Timelock::UntilBlock(block) => blocks_until(block),
+            Timelock::AfterBlocks(blocks) => {
+                // an unknown creation block counts as the current one, which is the safe side
+                let created: u64 = <UtxoCreation<T>>::get(outpoint)
+                    .map_or(current_block, |block| block.saturated_into());
+                blocks_until(created.saturating_add(blocks))
+            }
+            Timelock::UntilTime(_) => None,
+        };
+        remaining.map(|blocks| blocks.min(MAX_TIMELOCK_LONGEVITY))
+    }
+
+    /// Whether `timelock` is a time lock which still holds at the chain's time.
+    fn is_time_locked<T: Config>(timelock: &Timelock) -> bool {
+        match *timelock {
+            Timelock::UntilTime(time) => T::Time::now().as_secs() < time,
+            _ => false,
         }
     }
 
-    pub fn validate_transaction<T: Config>(
+    /// Checks that `witness` satisfies `destination`, which locks `utxo`. Some ways of spending
+    /// are only open from a point in time, which is returned as further locks on the input.
+    fn verify_witness<T: Config>(
+        utxo: &TransactionOutput,
+        destination: &Destination,
+        witness: &[Vec<u8>],
+        message: &[u8],
+    ) -> Result<Vec<Timelock>, Error<T>> {
+        match destination {
+            Destination::Pubkey(pub_key) => {
+                // a utxo locked to a single key is spent with a single signature
+                let signature = match witness {
+                    [signature] => signature,
+                    _ => return Err(Error::<T>::InvalidWitness),
+                };
+                verify_signature::<T>(utxo, pub_key, signature, message)?;
+                Ok(vec![])
+            }
+            Destination::Multisig { threshold, keys } => {
+                verify_multisig::<T>(utxo, *threshold, keys, witness, message)?;
+                Ok(vec![])
+            }
+            Destination::Htlc {
+                recipient,
+                refund,
+                hash,
+                timeout,
+            } => match witness {
+                [signature, preimage] => {
+                    ensure!(sha2_256(preimage) == hash.0, Error::<T>::InvalidPreimage);
+                    verify_signature::<T>(utxo, recipient, signature, message)?;
+                    Ok(vec![])
+                }
+                [signature] => {
+                    verify_signature::<T>(utxo, refund, signature, message)?;
+                    Ok(vec![Timelock::UntilBlock(*timeout)])
+                }
+                _ => Err(Error::<T>::InvalidWitness),
+            },
+            Destination::Script(script) => script::eval(script, witness, |pub_key, signature| {
+                T::SignatureVerifier::verify(utxo, pub_key, signature, message)
+            })
+            .map_err(|err| {
+                log::debug!("script failed: {:?}", err);
+                match err {
+                    ScriptError::NullFail => Error::<T>::SignatureFailure,
+                    ScriptError::UnsupportedSignatureMethod => {
+                        Error::<T>::UnsupportedSignatureMethod
+                    }
+                    _ => Error::<T>::ScriptFailed,
+                }
+            }),
+            Destination::ScriptHash(hash) => {
+                let (policy, witness) = witness.split_last().ok_or(Error::<T>::InvalidWitness)?;
+                ensure!(
+                    BlakeTwo256::hash(policy) == *hash,
+                    Error::<T>::InvalidPolicy
+                );
+                let policy =
+                    Destination::decode_all(policy).map_err(|_| Error::<T>::InvalidPolicy)?;
+                ensure!(policy.is_policy(), Error::<T>::InvalidPolicy);
+                verify_witness::<T>(utxo, &policy, witness, message)
+            }
+            Destination::Taproot(output_key) => match witness {
+                [signature] => {
+                    verify_signature::<T>(utxo, output_key, signature, message)?;
+                    Ok(vec![])
+                }
+                [policy_witness @ .., policy, control] => {
+                    let policy =
+                        Destination::decode_all(policy).map_err(|_| Error::<T>::InvalidPolicy)?;
+                    let control =
+                        ControlBlock::decode_all(control).map_err(|_| Error::<T>::InvalidPolicy)?;
+                    ensure!(
+                        policy.is_policy()
+                            && taproot::verify_script_path(output_key, &policy, &control),
+                        Error::<T>::InvalidPolicy
+                    );
+                    verify_witness::<T>(utxo, &policy, policy_witness, message)
+                }
+                [] => Err(Error::<T>::InvalidWitness),
+            },
+            Destination::Burn => Err(Error::<T>::InvalidWitness),
+        }
+    }
+
+    /// Checks that the witness holds valid signatures of at least `threshold` distinct `keys`.
+    fn verify_multisig<T: Config>(
+        utxo: &TransactionOutput,
+        threshold: u32,
+        keys: &[H256],
+        witness: &[Vec<u8>],
+        message: &[u8],
+    ) -> Result<(), Error<T>> {
+        let mut signers = BTreeSet::new();
+        for item in witness {
+            let (index, signature) = item.split_first().ok_or(Error::<T>::InvalidWitness)?;
+            let pub_key = keys
+                .get(*index as usize)
+                .ok_or(Error::<T>::InvalidWitness)?;
+            ensure!(signers.insert(index), Error::<T>::DuplicateSigner);
+            verify_signature::<T>(utxo, pub_key, signature, message)?;
+        }
+        ensure!(
+            signers.len() >= threshold as usize,
+            Error::<T>::ThresholdNotMet
+        );
+        Ok(())
+    }
+
+    /// The rewards to pay out in this block: MLT, and up to `MAX_REWARD_TOKENS` in all of the
+    /// tokens following `RewardCursor` in `RewardTotal`, wrapping around at its end.
+    fn reward_batch<T: Config>() -> Vec<(TokenID, Value)> {
+        let mlt = crate::TokenType::MLT as TokenID;
+        let mut rewards = Vec::new();
+        if <RewardTotal<T>>::contains_key(mlt) {
+            rewards.push((mlt, <RewardTotal<T>>::get(mlt)));
+        }
+
+        // `Blake2_128Concat` keys put the 16 byte hash before the encoded token id
+        let prefix = <RewardTotal<T>>::final_prefix();
+        let token_offset = prefix.len() + 16;
+        let start = <RewardCursor<T>>::get()
+            .map_or_else(|| prefix.to_vec(), <RewardTotal<T>>::hashed_key_for);
+
+        let mut key = start.clone();
+        let mut wrapped = false;
+        let mut last = None;
+        while rewards.len() < MAX_REWARD_TOKENS as usize {
+            let next = match sp_io::storage::next_key(&key).filter(|next| next.starts_with(&prefix))
+            {
+                Some(next) => next,
+                // past the end, go on from the first token up to the cursor
+                None if !wrapped => {
+                    wrapped = true;
+                    key = prefix.to_vec();
+                    continue;
+                }
+                None => break,
+            };
+            if wrapped && next > start {
+                break;
+            }
+            key = next;
+
+            let token_id = key
+                .get(token_offset..)
+                .and_then(|mut encoded| TokenID::decode(&mut encoded).ok());
+            let reward = unhashed::get::<Value>(&key);
+            if let (Some(token_id), Some(reward)) = (token_id, reward) {
+                if token_id != mlt {
+                    rewards.push((token_id, reward));
+                    last = Some(token_id);
+                }
+            }
+        }
+
+        match last {
+            Some(token_id) => <RewardCursor<T>>::put(token_id),
+            None => <RewardCursor<T>>::kill(),
+        }
+        rewards
+    }
+
+    pub(crate) fn disperse_reward<T: Config>(auths: &[H256], block_number: T::BlockNumber) {
+        // the rewards wait for authorities to be paid to
+        if auths.is_empty() {
+            return;
+        }
+
+        let mut rewards = reward_batch::<T>();
+        rewards.sort();
+
+        // Every reward is paid out in full, so that none is left behind as dust. What does not
+        // split evenly goes to one of the authorities, taking turns by block.
+        let count = auths.len() as Value;
+        let turn = (block_number.saturated_into::<u64>() % auths.len() as u64) as usize;
+        let mut outputs = Vec::new();
+        for (token_id, reward) in rewards {
+            log::debug!(
+                "disperse_reward:: reward total of token {}: {:?}",
+                token_id,
+                reward
+            );
+            <RewardTotal<T>>::remove(token_id);
+
+            let share_value: Value = reward / count;
+            let remainder = reward % count;
+            for (index, authority) in auths.iter().enumerate() {
+                let value = if index == turn {
+                    share_value + remainder
+                } else {
+                    share_value
+                };
+                if value > 0 {
+                    outputs.push(TransactionOutput::new(value, *authority).with_token_id(token_id));
+                }
+            }
+        }
+
+        // The rewards are the outputs of a coinbase transaction, whose only input points to
+        // the block number so that every block gets its own txid.
+        let coinbase = Transaction {
+            inputs: vec![TransactionInput::new(
+                OutPoint::new(H256::zero(), block_number.saturated_into::<u64>()),
+                vec![],
+            )],
+            // TODO: where do we get the header info?
+            outputs,
+        };
+        let txid = coinbase.txid();
+
+        for (index, utxo) in coinbase.outputs.into_iter().enumerate() {
+            let outpoint = OutPoint::new(txid, index as u64);
+            if !<UtxoStore<T>>::contains_key(outpoint) {
+                insert_utxo::<T>(outpoint, utxo);
+            }
+        }
+    }
+
+    pub fn validate_transaction<T: Config>(tx: &Transaction) -> Result<ValidTransaction, Error<T>> {
+        validate_transaction_fees::<T>(tx).map(|(validity, _)| validity)
+    }
+
+    /// Validates `tx` like `validate_transaction`, along with the fees it pays in each token.
+    /// The fees are only known once no input is missing.
+    pub fn validate_transaction_fees<T: Config>(
         tx: &Transaction,
-    ) -> Result<ValidTransaction, &'static str> {
+    ) -> Result<(ValidTransaction, BTreeMap<TokenID, Value>), Error<T>> {
         //ensure rather than assert to avoid panic
         //both inputs and outputs should contain at least 1 utxo
-        ensure!(!tx.inputs.is_empty(), "no inputs");
-        ensure!(!tx.outputs.is_empty(), "no outputs");
+        ensure!(!tx.inputs.is_empty(), Error::<T>::NoInputs);
+        ensure!(!tx.outputs.is_empty(), Error::<T>::NoOutputs);
 
         //ensure each input is used only a single time
         //maps each input into btree
@@ -210,11 +1540,14 @@ pub mod pallet {
         //https://github.com/substrate-developer-hub/utxo-workshop/blob/workshop/runtime/src/utxo.rs
         //input_map.len() > transaction.inputs.len() //THIS IS WRONG
         {
-            let input_map: BTreeMap<_, ()> = tx.inputs.iter().map(|input| (input, ())).collect();
+            // keyed on the outpoint alone, as inputs differing only in their witness or sighash
+            // still spend the same utxo
+            let input_map: BTreeMap<_, ()> =
+                tx.inputs.iter().map(|input| (input.outpoint, ())).collect();
             //we want map size and input size to be equal to ensure each is used only once
             ensure!(
                 input_map.len() == tx.inputs.len(),
-                "each input should be used only once"
+                Error::<T>::DuplicateInput
             );
         }
         //ensure each output is unique
@@ -226,7 +1559,7 @@ pub mod pallet {
             //check each output is defined only once
             ensure!(
                 out_map.len() == tx.outputs.len(),
-                "each output should be used once"
+                Error::<T>::DuplicateOutput
             );
         }
 
@@ -243,8 +1576,8 @@ pub mod pallet {
             .map(|output| (OutputHeader::new(output.header).token_id(), output.value))
             .collect();
 
+        let txid = tx.txid();
         let mut output_index: u64 = 0;
-        let simple_tx = get_simple_transaction(tx);
 
         // In order to avoid race condition in network we maintain a list of required utxos for a tx
         // Example of race condition:
@@ -252,42 +1585,152 @@ pub mod pallet {
         // In order to pay charlie alice must first send 10 coins to bob which creates a new utxo
         // If bob uses the new utxo to try and send the coins to charlie before charlie receives the alice to bob 10 coins utxo
         // then the tx from bob to charlie is invalid. By maintaining a list of required utxos we can ensure the tx can happen as and
-        // when the utxo is available. We use max longevity at the moment. That should be fixed.
+        // when the utxo is available.
+        // Utxos under a block lock are required the same way, with a tag nobody provides, and
+        // the transaction is only valid until the lock expires, or for `MAX_TIMELOCK_LONGEVITY`
+        // blocks at most. It cannot be included before, and is dropped from the pool then, to
+        // be submitted again. Nothing tells the pool when a time lock expires, so a spend
+        // under one is refused as future rather than queued.
+        // Each spent utxo is provided under a tag of its own, so that the pool sees two
+        // transactions spending the same utxo as conflicting, and keeps only one of them.
 
         let mut missing_utxos = Vec::new();
-        let mut new_utxos = Vec::new();
-        let mut reward = 0;
+        let mut timelocks = Vec::new();
+        let mut longevity = TransactionLongevity::MAX;
+        let mut time_locked = false;
+        let mut new_utxos: Vec<_> = tx
+            .inputs
+            .iter()
+            .map(|input| (b"spent", input.outpoint).encode())
+            .collect();
+        let mut input_nfts = BTreeSet::new();
+        let mut fees = BTreeMap::new();
+
+        // each ZkSnark input takes one costly proof check, so their number is bounded before any
+        // of them is checked
+        let zk_inputs = tx
+            .inputs
+            .iter()
+            .filter_map(|input| <UtxoStore<T>>::get(&input.outpoint))
+            .filter(|utxo| {
+                OutputHeader::new(utxo.header).sign_method() == Some(SignatureMethod::ZkSnark)
+            })
+            .count();
+        ensure!(zk_inputs <= MAX_ZK_INPUTS, Error::<T>::TooManyZkInputs);
+
+        let aggregating = tx.inputs.iter().any(BlsAggregate::is_key_only::<T>);
+        let mut bls_aggregate = BlsAggregate::default();
 
         // Check that inputs are valid
-        for input in tx.inputs.iter() {
+        for (index, input) in tx.inputs.iter().enumerate() {
+            ensure!(
+                input.witness.len() <= MAX_WITNESS_ITEMS
+                    && input.witness_size() <= MAX_WITNESS_SIZE,
+                Error::<T>::WitnessTooLarge
+            );
+
             if let Some(input_utxo) = <UtxoStore<T>>::get(&input.outpoint) {
-                ensure!(
-                    crypto::sr25519_verify(
-                        &SR25Sig::from_raw(*input.sig_script.as_fixed_bytes()),
-                        &simple_tx,
-                        &SR25Pub::from_h256(input_utxo.pub_key)
-                    ),
-                    "signature must be valid"
-                );
+                let message = signature_message(tx, index, input.sighash)
+                    .ok_or(Error::<T>::SigHashSingleWithoutOutput)?;
+                let spend_locks = match BlsAggregate::item(&input_utxo, &input.witness) {
+                    Some((pub_key, item)) if aggregating => {
+                        bls_aggregate.add::<T>(pub_key, item, message)?;
+                        vec![]
+                    }
+                    _ => verify_witness::<T>(
+                        &input_utxo,
+                        &input_utxo.destination,
+                        &input.witness,
+                        &message,
+                    )?,
+                };
+
+                for timelock in input_utxo.timelock.iter().chain(spend_locks.iter()) {
+                    time_locked |= is_time_locked::<T>(timelock);
+                    if let Some(remaining) = timelock_remaining::<T>(timelock, &input.outpoint) {
+                        timelocks.push((b"timelock", timelock).encode());
+                        longevity = longevity.min(remaining);
+                    }
+                }
+
+                if let Some(nft) = input_utxo.nft {
+                    input_nfts.insert((OutputHeader::new(input_utxo.header).token_id(), nft));
+                }
             } else {
-                missing_utxos.push(input.outpoint.clone().as_fixed_bytes().to_vec());
+                missing_utxos.push(input.outpoint.encode());
             }
         }
 
+        // the aggregate covers all of its inputs, so it waits for the missing ones
+        if missing_utxos.is_empty() {
+            ensure!(!time_locked, Error::<T>::TimelockNotExpired);
+            bls_aggregate.verify::<T>()?;
+        }
+
         // Check that outputs are valid
         for output in tx.outputs.iter() {
-            ensure!(output.value > 0, "output value must be nonzero");
-            let hash = BlakeTwo256::hash_of(&(&tx.encode(), output_index));
-            output_index = output_index.checked_add(1).ok_or("output index overflow")?;
-            ensure!(!<UtxoStore<T>>::contains_key(hash), "output already exists");
+            ensure!(output.value > 0, Error::<T>::ZeroOutputValue);
+            let outpoint = OutPoint::new(txid, output_index);
+            output_index = output_index
+                .checked_add(1)
+                .ok_or(Error::<T>::OutputIndexOverflow)?;
+            ensure!(
+                !<UtxoStore<T>>::contains_key(outpoint),
+                Error::<T>::OutputAlreadyExists
+            );
 
             // Check the header is valid
-            let res = output.header.as_tx_output_header().validate();
+            let res = output
+                .header
+                .as_tx_output_header()
+                .validate_with(token_exists::<T>);
             if !res {
                 log::error!("Header error. Signature or token id is not correct!");
             }
-            ensure!(res, "header error. Please check the logs.");
-            new_utxos.push(hash.as_fixed_bytes().to_vec());
+            ensure!(res, Error::<T>::InvalidHeader);
+            ensure!(
+                output.header.as_tx_output_header().sign_method() != Some(SignatureMethod::BLS)
+                    || T::SignatureVerifier::supports_bls(),
+                Error::<T>::UnsupportedSignatureMethod
+            );
+            ensure!(
+                output.destination.is_valid(),
+                Error::<T>::InvalidDestination
+            );
+            // the key path is a Schnorr signature of the tweaked key
+            if let Destination::Taproot(_) = output.destination {
+                ensure!(
+                    output.header.as_tx_output_header().sign_method()
+                        == Some(SignatureMethod::Schnorr),
+                    Error::<T>::InvalidDestination
+                );
+            }
+            // A ZkSnark output is locked to a single statement, and a BLS output to a single
+            // key, so that spending it takes one proof or signature, each charged as a pairing
+            // check, see `Transaction::pairing_checks`.
+            if matches!(
+                output.header.as_tx_output_header().sign_method(),
+                Some(SignatureMethod::ZkSnark) | Some(SignatureMethod::BLS)
+            ) {
+                ensure!(
+                    matches!(output.destination, Destination::Pubkey(_)),
+                    Error::<T>::InvalidDestination
+                );
+            }
+            // each output of an NFT class holds one NFT, and only those do
+            let token_id = output.header.as_tx_output_header().token_id();
+            match (&output.nft, is_nft_class::<T>(token_id)) {
+                (Some(nft), true) => {
+                    ensure!(output.value == 1 && nft.is_valid(), Error::<T>::InvalidNft)
+                }
+                (None, false) => (),
+                _ => frame_support::fail!(Error::<T>::InvalidNft),
+            }
+            // burnt outputs are not stored, so nothing can require them. A transaction burning
+            // all of its outputs still provides the utxos it spends.
+            if output.destination != Destination::Burn {
+                new_utxos.push(outpoint.encode());
+            }
         }
 
         // if no race condition, check the math
@@ -299,85 +1742,447 @@ pub mod pallet {
             for x in input_vec {
                 let value =
                     x.1.checked_add(*inputs_sum.get(&x.0).unwrap_or(&0))
-                        .ok_or("input value overflow")?;
+                        .ok_or(Error::<T>::InputValueOverflow)?;
                 inputs_sum.insert(x.0, value);
             }
             for x in out_vec {
                 let value =
                     x.1.checked_add(*outputs_sum.get(&x.0).unwrap_or(&0))
-                        .ok_or("output value overflow")?;
+                        .ok_or(Error::<T>::OutputValueOverflow)?;
                 outputs_sum.insert(x.0, value);
             }
 
+            // NFTs are neither created nor merged nor split: every NFT of the inputs goes to
+            // exactly one output, the same as it was.
+            for output in &tx.outputs {
+                if let Some(nft) = &output.nft {
+                    let token_id = OutputHeader::new(output.header).token_id();
+                    ensure!(
+                        input_nfts.remove(&(token_id, nft.clone())),
+                        Error::<T>::NftNotTransferred
+                    );
+                }
+            }
+            ensure!(input_nfts.is_empty(), Error::<T>::NftNotTransferred);
+
             for output_token in &outputs_sum {
                 match inputs_sum.get(&output_token.0) {
                     Some(input_value) => ensure!(
                         input_value >= &output_token.1,
-                        "output value must not exceed input value"
+                        Error::<T>::OutputExceedsInput
                     ),
-                    None => frame_support::fail!("input for the token not found"),
+                    None => frame_support::fail!(Error::<T>::TokenInputNotFound),
                 }
             }
 
-            // Reward at the moment only in MLT
-            reward = inputs_sum[&(crate::TokenType::MLT as TokenID)]
-                .checked_sub(outputs_sum[&(crate::TokenType::MLT as TokenID)])
-                .ok_or("reward underflow")?;
+            // Whatever is not sent on, in any token, is the fee of the block authors. It stays
+            // in circulation, so `TokenSupply` is unchanged.
+            for (token_id, input_value) in &inputs_sum {
+                let fee = input_value
+                    .checked_sub(*outputs_sum.get(token_id).unwrap_or(&0))
+                    .ok_or(Error::<T>::RewardUnderflow)?;
+                if fee > 0 {
+                    fees.insert(*token_id, fee);
+                }
+            }
+        }
+
+        // Fees in other tokens are not comparable, so only MLT raises the priority
+        let priority = *fees.get(&(crate::TokenType::MLT as TokenID)).unwrap_or(&0);
+        if missing_utxos.is_empty() {
+            ensure!(
+                priority >= minimum_fee::<T>(tx),
+                Error::<T>::InsufficientFee
+            );
         }
 
-        Ok(ValidTransaction {
-            priority: reward as u64,
-            requires: missing_utxos,
-            provides: new_utxos,
-            longevity: TransactionLongevity::MAX,
-            propagate: true,
-        })
+        missing_utxos.extend(timelocks);
+        Ok((
+            ValidTransaction {
+                priority: priority as u64,
+                requires: missing_utxos,
+                provides: new_utxos,
+                longevity,
+                propagate: true,
+            },
+            fees,
+        ))
+    }
+
+    /// The least MLT fee `tx` has to pay, according to its encoded size and its number of inputs
+    /// and outputs, so that the pool cannot be filled for free. Fees paid in other tokens do not
+    /// count towards it.
+    pub fn minimum_fee<T: Config>(tx: &Transaction) -> Value {
+        T::MinimumFeePerByte::get()
+            .saturating_mul(tx.encoded_size() as Value)
+            .saturating_add(T::MinimumFeePerInput::get().saturating_mul(tx.inputs.len() as Value))
+            .saturating_add(T::MinimumFeePerOutput::get().saturating_mul(tx.outputs.len() as Value))
+    }
+
+    /// Whether `token_id` is one of the built-in `TokenType`s or was issued on chain.
+    pub fn token_exists<T: Config>(token_id: TokenID) -> bool {
+        crate::TokenType::try_from(token_id as u128).is_ok()
+            || <TokenRegistry<T>>::contains_key(token_id)
+    }
+
+    /// Whether `token_id` is an NFT class.
+    pub fn is_nft_class<T: Config>(token_id: TokenID) -> bool {
+        <TokenRegistry<T>>::get(token_id).map_or(false, |info| info.kind == TokenKind::NonFungible)
     }
 
     /// Update storage to reflect changes made by transaction
-    /// Where each utxo key is a hash of the entire transaction and its order in the TransactionOutputs vector
+    /// Where each utxo key is the outpoint made of the txid and its order in the TransactionOutputs vector
     pub fn update_storage<T: Config>(
         tx: &Transaction,
-        reward: Value,
+        fees: &BTreeMap<TokenID, Value>,
     ) -> DispatchResultWithPostInfo {
-        // Calculate new reward total
-        let new_total = <RewardTotal<T>>::get()
-            .checked_add(reward)
-            .ok_or("Reward overflow")?;
+        // Calculate new reward totals, before writing any of them
+        let new_totals = fees
+            .iter()
+            .map(|(token_id, fee)| {
+                <RewardTotal<T>>::get(token_id)
+                    .checked_add(*fee)
+                    .map(|total| (*token_id, total))
+                    .ok_or(Error::<T>::RewardOverflow)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
 
-        log::debug!("update_storage:: reward total: {:?}", new_total);
-        <RewardTotal<T>>::put(new_total);
+        for (token_id, new_total) in new_totals {
+            log::debug!(
+                "update_storage:: reward total of token {}: {:?}",
+                token_id,
+                new_total
+            );
+            <RewardTotal<T>>::insert(token_id, new_total);
+        }
 
         // Removing spent UTXOs
         for input in &tx.inputs {
             log::debug!("removing {:?} in UtxoStore.", input.outpoint);
             <UtxoStore<T>>::remove(input.outpoint);
+            <UtxoCreation<T>>::remove(input.outpoint);
         }
 
+        let txid = tx.txid();
         let mut index: u64 = 0;
         for output in &tx.outputs {
-            let hash = BlakeTwo256::hash_of(&(&tx.encode(), index));
-            index = index.checked_add(1).ok_or("output index overflow")?;
-            log::debug!("inserting to UtxoStore {:?} as key {:?}", output, hash);
-            <UtxoStore<T>>::insert(hash, Some(output));
+            let outpoint = OutPoint::new(txid, index);
+            index = index
+                .checked_add(1)
+                .ok_or(Error::<T>::OutputIndexOverflow)?;
+            let token_id = OutputHeader::new(output.header).token_id();
+            let burnt = output.destination == Destination::Burn;
+
+            if let Some(nft) = &output.nft {
+                if burnt {
+                    <Nfts<T>>::remove(token_id, nft.content_hash);
+                    Pallet::<T>::deposit_event(Event::<T>::NftBurned(token_id, nft.content_hash));
+                } else {
+                    <Nfts<T>>::insert(token_id, nft.content_hash, outpoint);
+                    Pallet::<T>::deposit_event(Event::<T>::NftTransferred(
+                        token_id,
+                        nft.content_hash,
+                        outpoint,
+                    ));
+                }
+            }
+
+            if burnt {
+                burn::<T>(token_id, output.value);
+                continue;
+            }
+            log::debug!("inserting to UtxoStore {:?} as key {:?}", output, outpoint);
+            insert_utxo::<T>(outpoint, output.clone());
         }
 
         Ok(().into())
     }
 
+    /// Takes burnt `value` out of the circulating supply of `token_id`.
+    fn burn<T: Config>(token_id: TokenID, value: Value) {
+        if <TokenRegistry<T>>::contains_key(token_id) {
+            <TokenSupply<T>>::mutate(token_id, |supply| *supply = supply.saturating_sub(value));
+        }
+        Pallet::<T>::deposit_event(Event::<T>::TokenBurned(token_id, value));
+    }
+
+    /// Registers the token `info`, with its initial supply, under the next token id.
+    fn register_token<T: Config>(info: TokenInfo) -> Result<TokenID, Error<T>> {
+        let token_id = <NextTokenId<T>>::get();
+        let next_token_id = token_id.checked_add(1).ok_or(Error::<T>::TokenIdOverflow)?;
+
+        <TokenSupply<T>>::insert(token_id, info.initial_supply);
+        <TokenRegistry<T>>::insert(token_id, info);
+        <NextTokenId<T>>::put(next_token_id);
+        Ok(token_id)
+    }
+
+    /// Checks the issuer's sr25519 `signature` of a mint `message`.
+    fn verify_issuer<T: Config>(
+        info: &TokenInfo,
+        message: &[u8],
+        signature: &H512,
+    ) -> Result<(), Error<T>> {
+        ensure!(
+            crypto::sr25519_verify(
+                &SR25Sig::from_raw(signature.0),
+                message,
+                &SR25Pub::from_h256(info.issuer)
+            ),
+            Error::<T>::SignatureFailure
+        );
+        Ok(())
+    }
+
+    /// Where the utxo of the mint numbered `mints` is stored. Like a coinbase, the minting
+    /// transaction has a single input pointing to that number, so that every mint gets its own
+    /// txid. The input is tagged, so that a mint never gets the txid of a coinbase, whose input
+    /// points to a block number instead.
+    pub fn mint_outpoint(utxo: &TransactionOutput, mints: u64) -> OutPoint {
+        Transaction {
+            inputs: vec![TransactionInput::new(
+                OutPoint::new(BlakeTwo256::hash(b"mint"), mints),
+                vec![],
+            )],
+            outputs: vec![utxo.clone()],
+        }
+        .outpoint(0)
+    }
+
+    /// Fails unless `tx` can be applied now, rather than waiting in the pool for the utxos it
+    /// `requires`.
+    fn ensure_ready<T: Config>(
+        tx: &Transaction,
+        validity: &ValidTransaction,
+    ) -> Result<(), Error<T>> {
+        if !validity.requires.is_empty() {
+            let missing_inputs = tx
+                .inputs
+                .iter()
+                .any(|input| !<UtxoStore<T>>::contains_key(&input.outpoint));
+            ensure!(!missing_inputs, Error::<T>::MissingInputs);
+            // all inputs are there, so one of them is still locked
+            frame_support::fail!(Error::<T>::TimelockNotExpired);
+        }
+        Ok(())
+    }
+
+    /// How the pool is told about `err`. A spend of a utxo still under a timelock will be valid
+    /// later, so a block author skips it as such rather than as invalid.
+    fn validity_error<T: Config>(err: Error<T>) -> TransactionValidityError {
+        match err {
+            Error::<T>::TimelockNotExpired => InvalidTransaction::Future.into(),
+            err => InvalidTransaction::from(err).into(),
+        }
+    }
+
+    /// Stores a new utxo along with the block creating it.
+    fn insert_utxo<T: Config>(outpoint: OutPoint, utxo: TransactionOutput) {
+        <UtxoStore<T>>::insert(outpoint, Some(utxo));
+        <UtxoCreation<T>>::insert(outpoint, <frame_system::Pallet<T>>::block_number());
+    }
+
     #[pallet::call]
     impl<T: Config> Pallet<T> {
-        #[pallet::weight(T::WeightInfo::spend(tx.inputs.len().saturating_add(tx.outputs.len()) as u32))]
-        pub fn spend(_origin: OriginFor<T>, tx: Transaction) -> DispatchResultWithPostInfo {
-            let tx_validity = validate_transaction::<T>(&tx)?;
-            ensure!(tx_validity.requires.is_empty(), "missing inputs");
+        #[pallet::weight(T::WeightInfo::spend(
+            tx.inputs.len().saturating_add(tx.outputs.len()) as u32,
+            tx.witness_size() as u32,
+            tx.pairing_checks() as u32,
+            tx.witness_items() as u32,
+        ))]
+        pub fn spend(origin: OriginFor<T>, tx: Transaction) -> DispatchResultWithPostInfo {
+            // utxos are authorised by their signatures, so no account is needed to submit
+            ensure_none(origin)?;
 
-            // Now in tx_validity.priority only amount of MLT
-            update_storage::<T>(&tx, tx_validity.priority as Value)?;
+            let (tx_validity, fees) = validate_transaction_fees::<T>(&tx)?;
+            ensure_ready::<T>(&tx, &tx_validity)?;
+
+            update_storage::<T>(&tx, &fees)?;
 
             Self::deposit_event(Event::<T>::TransactionSuccess(tx));
             Ok(().into())
         }
+
+        /// Registers a new token, and mints its initial `supply` to the sr25519 key `issuer`.
+        /// The issuer may `mint` more later, up to `max_supply` if there is one.
+        #[pallet::weight(T::WeightInfo::issue_token())]
+        pub fn issue_token(
+            origin: OriginFor<T>,
+            ticker: Vec<u8>,
+            decimals: u8,
+            issuer: H256,
+            supply: Value,
+            max_supply: Option<Value>,
+        ) -> DispatchResultWithPostInfo {
+            // no utxo pays for the registration, so an account does
+            ensure_signed(origin)?;
+            ensure!(is_valid_ticker(&ticker), Error::<T>::InvalidTicker);
+            ensure!(decimals <= MAX_DECIMALS, Error::<T>::TooManyDecimals);
+            ensure!(supply > 0, Error::<T>::ZeroSupply);
+            ensure!(
+                max_supply.map_or(true, |max_supply| supply <= max_supply),
+                Error::<T>::SupplyCapExceeded
+            );
+
+            let token_id = register_token::<T>(TokenInfo {
+                ticker,
+                decimals,
+                issuer,
+                initial_supply: supply,
+                max_supply,
+                mints: 0,
+                kind: TokenKind::Fungible,
+            })?;
+
+            // The supply is the output of a transaction without inputs, like the genesis
+            // utxos. Its token id makes the txid unique.
+            let utxo = TransactionOutput::new(supply, issuer).with_token_id(token_id);
+            let outpoint = Transaction {
+                inputs: vec![],
+                outputs: vec![utxo.clone()],
+            }
+            .outpoint(0);
+            insert_utxo::<T>(outpoint, utxo);
+
+            Self::deposit_event(Event::<T>::TokenIssued(token_id, outpoint));
+            Ok(().into())
+        }
+
+        /// Mints `amount` more of an issued token to the sr25519 key `recipient`, with the
+        /// issuer's sr25519 `signature` of the `mint_message`.
+        #[pallet::weight(T::WeightInfo::mint())]
+        pub fn mint(
+            origin: OriginFor<T>,
+            token_id: TokenID,
+            amount: Value,
+            recipient: H256,
+            signature: H512,
+        ) -> DispatchResultWithPostInfo {
+            ensure_signed(origin)?;
+            let mut info = <TokenRegistry<T>>::get(token_id).ok_or(Error::<T>::TokenNotFound)?;
+            ensure!(info.kind == TokenKind::Fungible, Error::<T>::WrongTokenKind);
+            ensure!(amount > 0, Error::<T>::ZeroSupply);
+
+            let message = mint_message(token_id, info.mints, amount, &recipient);
+            verify_issuer::<T>(&info, &message, &signature)?;
+
+            let supply = <TokenSupply<T>>::get(token_id)
+                .checked_add(amount)
+                .filter(|supply| {
+                    info.max_supply
+                        .map_or(true, |max_supply| *supply <= max_supply)
+                })
+                .ok_or(Error::<T>::SupplyCapExceeded)?;
+
+            let utxo = TransactionOutput::new(amount, recipient).with_token_id(token_id);
+            let outpoint = mint_outpoint(&utxo, info.mints);
+            ensure!(
+                !<UtxoStore<T>>::contains_key(outpoint),
+                Error::<T>::OutputAlreadyExists
+            );
+
+            info.mints = info.mints.saturating_add(1);
+            <TokenRegistry<T>>::insert(token_id, info);
+            <TokenSupply<T>>::insert(token_id, supply);
+            insert_utxo::<T>(outpoint, utxo);
+
+            Self::deposit_event(Event::<T>::TokenMinted(token_id, outpoint));
+            Ok(().into())
+        }
+
+        /// Registers a new NFT class, whose NFTs the sr25519 key `issuer` may `mint_nft`.
+        #[pallet::weight(T::WeightInfo::issue_nft_class())]
+        pub fn issue_nft_class(
+            origin: OriginFor<T>,
+            ticker: Vec<u8>,
+            issuer: H256,
+        ) -> DispatchResultWithPostInfo {
+            ensure_signed(origin)?;
+            ensure!(is_valid_ticker(&ticker), Error::<T>::InvalidTicker);
+
+            let token_id = register_token::<T>(TokenInfo {
+                ticker,
+                decimals: 0,
+                issuer,
+                initial_supply: 0,
+                max_supply: None,
+                mints: 0,
+                kind: TokenKind::NonFungible,
+            })?;
+
+            Self::deposit_event(Event::<T>::NftClassIssued(token_id));
+            Ok(().into())
+        }
+
+        /// Mints `nft` in the NFT class `token_id` to the sr25519 key `recipient`, with the
+        /// issuer's sr25519 `signature` of the `mint_nft_message`.
+        #[pallet::weight(T::WeightInfo::mint_nft())]
+        pub fn mint_nft(
+            origin: OriginFor<T>,
+            token_id: TokenID,
+            nft: NftData,
+            recipient: H256,
+            signature: H512,
+        ) -> DispatchResultWithPostInfo {
+            ensure_signed(origin)?;
+            let mut info = <TokenRegistry<T>>::get(token_id).ok_or(Error::<T>::TokenNotFound)?;
+            ensure!(
+                info.kind == TokenKind::NonFungible,
+                Error::<T>::WrongTokenKind
+            );
+            ensure!(nft.is_valid(), Error::<T>::InvalidNft);
+            ensure!(
+                !<Nfts<T>>::contains_key(token_id, nft.content_hash),
+                Error::<T>::NftAlreadyExists
+            );
+
+            let message = mint_nft_message(token_id, info.mints, &nft, &recipient);
+            verify_issuer::<T>(&info, &message, &signature)?;
+
+            let content_hash = nft.content_hash;
+            let utxo = TransactionOutput::new(1, recipient)
+                .with_token_id(token_id)
+                .with_nft(nft);
+            let outpoint = mint_outpoint(&utxo, info.mints);
+            ensure!(
+                !<UtxoStore<T>>::contains_key(outpoint),
+                Error::<T>::OutputAlreadyExists
+            );
+
+            info.mints = info.mints.saturating_add(1);
+            <TokenRegistry<T>>::insert(token_id, info);
+            <TokenSupply<T>>::mutate(token_id, |supply| *supply = supply.saturating_add(1));
+            <Nfts<T>>::insert(token_id, content_hash, outpoint);
+            insert_utxo::<T>(outpoint, utxo);
+
+            Self::deposit_event(Event::<T>::NftMinted(token_id, content_hash, outpoint));
+            Ok(().into())
+        }
+    }
+
+    #[pallet::validate_unsigned]
+    impl<T: Config> ValidateUnsigned for Pallet<T> {
+        type Call = Call<T>;
+
+        fn validate_unsigned(_source: TransactionSource, call: &Self::Call) -> TransactionValidity {
+            if let Call::spend(ref tx) = call {
+                validate_transaction::<T>(tx).map_err(validity_error)
+            } else {
+                Err(InvalidTransaction::Call.into())
+            }
+        }
+
+        // A block has no room for transactions still waiting for their inputs: they would fail
+        // in `spend` without paying any fee.
+        fn pre_dispatch(call: &Self::Call) -> Result<(), TransactionValidityError> {
+            if let Call::spend(ref tx) = call {
+                validate_transaction::<T>(tx)
+                    .and_then(|validity| ensure_ready::<T>(tx, &validity))
+                    .map_err(validity_error)
+            } else {
+                Err(InvalidTransaction::Call.into())
+            }
+        }
     }
 
     #[pallet::genesis_config]
@@ -399,9 +2204,18 @@ pub mod pallet {
     #[pallet::genesis_build]
     impl<T: Config> GenesisBuild<T> for GenesisConfig<T> {
         fn build(&self) {
-            self.genesis_utxos.iter().cloned().for_each(|u| {
-                UtxoStore::<T>::insert(BlakeTwo256::hash_of(&u), Some(u));
-            });
+            // The genesis utxos are the outputs of a transaction without inputs.
+            let genesis_tx = Transaction {
+                inputs: vec![],
+                outputs: self.genesis_utxos.clone(),
+            };
+            let txid = genesis_tx.txid();
+
+            for (index, u) in genesis_tx.outputs.into_iter().enumerate() {
+                insert_utxo::<T>(OutPoint::new(txid, index as u64), u);
+            }
+
+            StorageVersion::<T>::put(Releases::V8);
         }
     }
 }
@@ -0,0 +1,291 @@
+// Copyright (c) 2021 RBB S.r.l
+// opensource@mintlayer.org
+// SPDX-License-Identifier: MIT
+// Licensed under the MIT License;
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://spdx.org/licenses/MIT
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// Author(s): C. Yap
+
+use crate::{
+    Config, Destination, Event, OutPoint, OutputHeader, Pallet, Releases, RewardTotal,
+    SignatureMethod, StorageVersion, TXOutputHeader, Timelock, TokenID, TokenType,
+    TransactionOutput, UtxoCreation, UtxoStore, Value,
+};
+use codec::{Decode, Encode};
+use frame_support::{
+    sp_io,
+    sp_runtime::traits::{BlakeTwo256, Hash},
+    storage::{unhashed, StoragePrefixedMap},
+    traits::Get,
+    weights::Weight,
+    RuntimeDebug,
+};
+use sp_core::{sp_std::vec::Vec, H256};
+
+// Legacy keys were hashed with `Blake2_256`, which leaves 32 bytes after the map prefix.
+const LEGACY_KEY_LEN: usize = 32;
+// `Blake2_128Concat` keys put the 16 byte hash before the encoded key.
+const BLAKE2_128_LEN: usize = 16;
+
+/// A utxo as stored up to `Releases::V3`, locked to a bare key.
+#[derive(Clone, Encode, Decode, Eq, PartialEq, RuntimeDebug)]
+pub struct LegacyTransactionOutput {
+    pub value: Value,
+    pub pub_key: H256,
+    pub header: TXOutputHeader,
+}
+
+/// A utxo as stored in `Releases::V4`, before timelocks.
+#[derive(Clone, Encode, Decode, Eq, PartialEq, RuntimeDebug)]
+pub struct TransactionOutputV4 {
+    pub value: Value,
+    pub destination: Destination,
+    pub header: TXOutputHeader,
+}
+
+/// A utxo as stored from `Releases::V5` to `Releases::V6`, before NFTs.
+#[derive(Clone, Encode, Decode, Eq, PartialEq, RuntimeDebug)]
+pub struct TransactionOutputV6 {
+    pub value: Value,
+    pub destination: Destination,
+    pub header: TXOutputHeader,
+    pub timelock: Option<Timelock>,
+}
+
+impl From<LegacyTransactionOutput> for TransactionOutputV4 {
+    fn from(utxo: LegacyTransactionOutput) -> Self {
+        TransactionOutputV4 {
+            value: utxo.value,
+            destination: Destination::Pubkey(utxo.pub_key),
+            header: utxo.header,
+        }
+    }
+}
+
+impl From<TransactionOutputV4> for TransactionOutputV6 {
+    fn from(utxo: TransactionOutputV4) -> Self {
+        TransactionOutputV6 {
+            value: utxo.value,
+            destination: utxo.destination,
+            header: utxo.header,
+            timelock: None,
+        }
+    }
+}
+
+impl From<TransactionOutputV6> for TransactionOutput {
+    fn from(utxo: TransactionOutputV6) -> Self {
+        TransactionOutput {
+            value: utxo.value,
+            destination: utxo.destination,
+            header: utxo.header,
+            timelock: utxo.timelock,
+            nft: None,
+        }
+    }
+}
+
+/// Brings the storage up to the latest `Releases` version.
+pub fn migrate<T: Config>() -> Weight {
+    let mut weight = T::DbWeight::get().reads(1);
+
+    if StorageVersion::<T>::get() == Releases::V1 {
+        weight = weight.saturating_add(migrate_to_outpoints::<T>());
+        StorageVersion::<T>::put(Releases::V2);
+        weight = weight.saturating_add(T::DbWeight::get().writes(1));
+    }
+
+    if StorageVersion::<T>::get() == Releases::V2 {
+        weight = weight.saturating_add(migrate_to_schnorr_headers::<T>());
+        StorageVersion::<T>::put(Releases::V3);
+        weight = weight.saturating_add(T::DbWeight::get().writes(1));
+    }
+
+    if StorageVersion::<T>::get() == Releases::V3 {
+        weight = weight.saturating_add(migrate_to_destinations::<T>());
+        StorageVersion::<T>::put(Releases::V4);
+        weight = weight.saturating_add(T::DbWeight::get().writes(1));
+    }
+
+    if StorageVersion::<T>::get() == Releases::V4 {
+        weight = weight.saturating_add(migrate_to_timelocks::<T>());
+        StorageVersion::<T>::put(Releases::V5);
+        weight = weight.saturating_add(T::DbWeight::get().writes(1));
+    }
+
+    if StorageVersion::<T>::get() == Releases::V5 {
+        weight = weight.saturating_add(migrate_to_creation_blocks::<T>());
+        StorageVersion::<T>::put(Releases::V6);
+        weight = weight.saturating_add(T::DbWeight::get().writes(1));
+    }
+
+    if StorageVersion::<T>::get() == Releases::V6 {
+        weight = weight.saturating_add(migrate_to_nfts::<T>());
+        StorageVersion::<T>::put(Releases::V7);
+        weight = weight.saturating_add(T::DbWeight::get().writes(1));
+    }
+
+    if StorageVersion::<T>::get() == Releases::V7 {
+        weight = weight.saturating_add(migrate_to_token_rewards::<T>());
+        StorageVersion::<T>::put(Releases::V8);
+        weight = weight.saturating_add(T::DbWeight::get().writes(1));
+    }
+
+    weight
+}
+
+/// Where a utxo stored under the opaque `BlakeTwo256(tx.encode(), index)` hash now lives.
+///
+/// The old `UtxoStore` hasher was not reversible, so the migration can only see
+/// `blake2_256(old_hash)`. That becomes the txid of the new outpoint.
+pub fn legacy_outpoint(old_hash: H256) -> OutPoint {
+    OutPoint::new(BlakeTwo256::hash(old_hash.as_bytes()), 0)
+}
+
+// The raw keys of all `UtxoStore` entries.
+fn raw_utxo_keys<T: Config>() -> Vec<Vec<u8>> {
+    let prefix = UtxoStore::<T>::final_prefix();
+
+    let mut keys = Vec::new();
+    let mut key = prefix.to_vec();
+    while let Some(next) = sp_io::storage::next_key(&key) {
+        if !next.starts_with(&prefix) {
+            break;
+        }
+        keys.push(next.clone());
+        key = next;
+    }
+    keys
+}
+
+/// Moves every `UtxoStore` entry keyed by a legacy `H256` to its `legacy_outpoint`.
+/// Entries already keyed by an `OutPoint` are left alone, so running it twice is a no-op.
+/// Each move is announced with `Event::LegacyUtxoMigrated`, so holders can find their coins.
+pub fn migrate_to_outpoints<T: Config>() -> Weight {
+    let prefix_len = UtxoStore::<T>::final_prefix().len();
+
+    let raw_keys = raw_utxo_keys::<T>();
+    let reads = raw_keys.len() as Weight;
+    let legacy_keys: Vec<_> = raw_keys
+        .into_iter()
+        .filter(|raw_key| raw_key.len() == prefix_len + LEGACY_KEY_LEN)
+        .collect();
+
+    // taking the entry, putting it under the outpoint and depositing the event
+    let writes = legacy_keys.len() as Weight * 3;
+    for raw_key in legacy_keys {
+        let outpoint = OutPoint::new(H256::from_slice(&raw_key[prefix_len..]), 0);
+        if let Some(utxo) = unhashed::take::<Option<LegacyTransactionOutput>>(&raw_key) {
+            log::info!("migrating legacy utxo {:?} to {:?}", utxo, outpoint);
+            unhashed::put(&UtxoStore::<T>::hashed_key_for(&outpoint), &utxo);
+            if let Some(utxo) = utxo {
+                Pallet::<T>::deposit_event(Event::<T>::LegacyUtxoMigrated(utxo.pub_key, outpoint));
+            }
+        }
+    }
+
+    T::DbWeight::get().reads_writes(reads, writes)
+}
+
+/// `TransactionOutput::new` used to leave the signature method as BLS, although the outputs
+/// were always locked to sr25519 keys. Such outputs now claim the Schnorr method they really use.
+pub fn migrate_to_schnorr_headers<T: Config>() -> Weight {
+    let raw_keys = raw_utxo_keys::<T>();
+    let translated = raw_keys.len() as Weight;
+    for raw_key in raw_keys {
+        if let Some(Some(mut utxo)) = unhashed::get::<Option<LegacyTransactionOutput>>(&raw_key) {
+            let mut header = OutputHeader::new(utxo.header);
+            if header.sign_method() == Some(SignatureMethod::BLS) {
+                header.set_sign_method(SignatureMethod::Schnorr);
+                utxo.header = header.as_u128();
+                unhashed::put(&raw_key, &Some(utxo));
+            }
+        }
+    }
+
+    T::DbWeight::get().reads_writes(translated, translated)
+}
+
+/// Locks the utxos stored with a bare key to the equivalent `Destination::Pubkey`.
+pub fn migrate_to_destinations<T: Config>() -> Weight {
+    let raw_keys = raw_utxo_keys::<T>();
+    let translated = raw_keys.len() as Weight;
+    for raw_key in raw_keys {
+        if let Some(utxo) = unhashed::get::<Option<LegacyTransactionOutput>>(&raw_key) {
+            unhashed::put(&raw_key, &utxo.map(TransactionOutputV4::from));
+        }
+    }
+
+    T::DbWeight::get().reads_writes(translated, translated)
+}
+
+/// Existing utxos are not under any timelock.
+pub fn migrate_to_timelocks<T: Config>() -> Weight {
+    let raw_keys = raw_utxo_keys::<T>();
+    let translated = raw_keys.len() as Weight;
+    for raw_key in raw_keys {
+        if let Some(utxo) = unhashed::get::<Option<TransactionOutputV4>>(&raw_key) {
+            unhashed::put(&raw_key, &utxo.map(TransactionOutputV6::from));
+        }
+    }
+
+    T::DbWeight::get().reads_writes(translated, translated)
+}
+
+/// The creation block of existing utxos is not known, so they are recorded as created in the
+/// block of the migration. Relative timelocks can only start from there.
+///
+/// The values are still stored in the `TransactionOutputV6` layout at this step, so the outpoints
+/// are read from the raw keys rather than by decoding the map.
+pub fn migrate_to_creation_blocks<T: Config>() -> Weight {
+    let current_block = frame_system::Pallet::<T>::block_number();
+    let prefix_len = UtxoStore::<T>::final_prefix().len();
+
+    let raw_keys = raw_utxo_keys::<T>();
+    let reads = raw_keys.len() as Weight;
+    let outpoints: Vec<OutPoint> = raw_keys
+        .iter()
+        .filter_map(|raw_key| raw_key.get(prefix_len + BLAKE2_128_LEN..))
+        .filter_map(|mut encoded| OutPoint::decode(&mut encoded).ok())
+        .filter(|outpoint| !UtxoCreation::<T>::contains_key(outpoint))
+        .collect();
+
+    let count = outpoints.len() as Weight;
+    for outpoint in outpoints {
+        UtxoCreation::<T>::insert(outpoint, current_block);
+    }
+
+    T::DbWeight::get().reads_writes(reads.saturating_mul(2), count)
+}
+
+/// Existing utxos hold no NFT, since no NFT class could be issued before.
+pub fn migrate_to_nfts<T: Config>() -> Weight {
+    let mut translated: Weight = 0;
+    UtxoStore::<T>::translate_values(|utxo: Option<TransactionOutputV6>| {
+        translated += 1;
+        Some(utxo.map(TransactionOutput::from))
+    });
+
+    T::DbWeight::get().reads_writes(translated, translated)
+}
+
+/// `RewardTotal` used to be a single value, of fees paid in MLT only. It moves to the MLT entry
+/// of the map, whose prefix is the key of the old value.
+pub fn migrate_to_token_rewards<T: Config>() -> Weight {
+    let legacy_key = RewardTotal::<T>::final_prefix();
+    if let Some(reward) = unhashed::take::<Value>(&legacy_key) {
+        log::info!("migrating reward total {:?} to MLT", reward);
+        RewardTotal::<T>::insert(TokenType::MLT as TokenID, reward);
+    }
+
+    T::DbWeight::get().reads_writes(1, 2)
+}
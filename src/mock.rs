@@ -16,23 +16,47 @@
 // Author(s): C. Yap
 
 use crate as pallet_utxo;
-use pallet_utxo::TransactionOutput;
+use ark_bls12_381::{Bls12_381, Fr, G1Affine, G2Projective};
+use ark_ec::{AffineCurve, ProjectiveCurve};
+use ark_ff::{PrimeField, Zero};
+use ark_groth16::{create_random_proof, generate_random_parameters, ProvingKey};
+use ark_relations::{
+    lc,
+    r1cs::{ConstraintSynthesizer, ConstraintSystemRef, SynthesisError},
+};
+use ark_serialize::CanonicalSerialize;
+use ark_std::{
+    rand::{rngs::StdRng, SeedableRng},
+    test_rng,
+};
+use pallet_utxo::{
+    bls,
+    zk::{self, ZkProof},
+};
+use pallet_utxo::{
+    bls_key_hash, BlsVerifier, DefaultVerifier, OutPoint, SignatureError, SignatureVerifier,
+    Transaction, TransactionOutput, Value, BLS_PUBLIC_KEY_SIZE, BLS_SIGNATURE_SIZE,
+};
 
 use frame_support::{
     parameter_types,
-    sp_io::TestExternalities,
+    sp_io::{hashing::blake2_256, TestExternalities},
     sp_runtime::{
         testing::Header,
         traits::{BlakeTwo256, IdentityLookup},
     },
-    traits::GenesisBuild,
+    traits::{GenesisBuild, Get},
 };
 use sp_consensus_aura::sr25519::AuthorityId as AuraId;
-use sp_core::{sp_std::vec, sr25519::Public, testing::SR25519, H256};
+use sp_core::{
+    sp_std::vec,
+    sr25519::{self, Public},
+    testing::{TaskExecutor, SR25519},
+    traits::TaskExecutorExt,
+    Pair, H256,
+};
 use sp_keystore::{testing::KeyStore, KeystoreExt, SyncCryptoStore};
-
-// need to manually import this crate since its no include by default
-use hex_literal::hex;
+use std::cell::RefCell;
 
 pub type UncheckedExtrinsic = frame_system::mocking::MockUncheckedExtrinsic<Test>;
 pub type Block = frame_system::mocking::MockBlock<Test>;
@@ -40,9 +64,17 @@ pub type Block = frame_system::mocking::MockBlock<Test>;
 pub const ALICE_PHRASE: &str =
     "news slush supreme milk chapter athlete soap sausage put clutch what kitten";
 
-// BlakeHash of TransactionOutput::new(100, H256::from(alice_pub_key)) in fn new_test_ext()
-pub const GENESIS_UTXO: [u8; 32] =
-    hex!("3a6911b761bd0111409340b3032556b16be8299384a9e5bf7806ef8df7e098b0");
+// Outpoint of TransactionOutput::new(100, H256::from(alice_pub_key)) in fn new_test_ext()
+pub fn genesis_outpoint() -> OutPoint {
+    let alice_pub_key = sr25519::Pair::from_string(ALICE_PHRASE, None)
+        .unwrap()
+        .public();
+    let genesis_tx = Transaction {
+        inputs: vec![],
+        outputs: vec![TransactionOutput::new(100, H256::from(alice_pub_key))],
+    };
+    genesis_tx.outpoint(0)
+}
 
 // Configure a mock runtime to test the pallet.
 frame_support::construct_runtime!(
@@ -53,7 +85,7 @@ frame_support::construct_runtime!(
     {
         System: frame_system::{Pallet, Call, Config, Storage, Event<T>},
         Timestamp: pallet_timestamp::{Pallet, Call, Storage, Inherent},
-        Utxo: pallet_utxo::{Pallet, Call, Config<T>, Storage, Event<T>},
+        Utxo: pallet_utxo::{Pallet, Call, Config<T>, Storage, Event<T>, ValidateUnsigned},
         Aura: pallet_aura::{Pallet, Call, Config<T>, Storage},
     }
 );
@@ -94,10 +126,11 @@ impl frame_system::Config for Test {
     type OnSetCode = ();
 }
 
-// required by pallet_aura
+// required by pallet_aura, and the clock of timelocks
 impl pallet_timestamp::Config for Test {
     type Moment = u64;
-    type OnTimestampSet = Aura;
+    // tests set the time without moving aura's slot
+    type OnTimestampSet = ();
     type MinimumPeriod = MinimumPeriod;
     type WeightInfo = ();
 }
@@ -106,10 +139,226 @@ impl pallet_aura::Config for Test {
     type AuthorityId = AuraId;
 }
 
+thread_local! {
+    pub static REVOKED_KEYS: RefCell<Vec<H256>> = RefCell::new(vec![]);
+    // whether the runtime supports BLS, as if its verifier had no `BlsVerifier` otherwise
+    pub static BLS_SUPPORTED: RefCell<bool> = RefCell::new(true);
+    // no minimum fee unless a test sets one
+    pub static FEE_PER_BYTE: RefCell<Value> = RefCell::new(0);
+    pub static FEE_PER_INPUT: RefCell<Value> = RefCell::new(0);
+    pub static FEE_PER_OUTPUT: RefCell<Value> = RefCell::new(0);
+}
+
+pub struct MinimumFeePerByte;
+
+impl Get<Value> for MinimumFeePerByte {
+    fn get() -> Value {
+        FEE_PER_BYTE.with(|fee| *fee.borrow())
+    }
+}
+
+pub struct MinimumFeePerInput;
+
+impl Get<Value> for MinimumFeePerInput {
+    fn get() -> Value {
+        FEE_PER_INPUT.with(|fee| *fee.borrow())
+    }
+}
+
+pub struct MinimumFeePerOutput;
+
+impl Get<Value> for MinimumFeePerOutput {
+    fn get() -> Value {
+        FEE_PER_OUTPUT.with(|fee| *fee.borrow())
+    }
+}
+
+// An insecure stand-in for BLS12-381 with the same sizes, which spares the tests of spending
+// BLS utxos the pairings of `ArkworksBls`. Anyone can sign for a key: a signature hashes the
+// key and the message, and an aggregate is the XOR of its signatures.
+pub struct TestBls;
+
+pub fn test_bls_key(seed: u8) -> [u8; BLS_PUBLIC_KEY_SIZE] {
+    let mut key = [seed; BLS_PUBLIC_KEY_SIZE];
+    key[..32].copy_from_slice(&blake2_256(&[seed]));
+    key
+}
+
+pub fn test_bls_sign(key: &[u8], message: &[u8]) -> Vec<u8> {
+    (0..BLS_SIGNATURE_SIZE / 32)
+        .flat_map(|chunk| blake2_256(&[key, message, &[chunk as u8]].concat()).to_vec())
+        .collect()
+}
+
+pub fn test_bls_aggregate(signatures: &[Vec<u8>]) -> Vec<u8> {
+    signatures
+        .iter()
+        .fold(vec![0u8; BLS_SIGNATURE_SIZE], |mut aggregate, signature| {
+            aggregate
+                .iter_mut()
+                .zip(signature)
+                .for_each(|(byte, other)| *byte ^= other);
+            aggregate
+        })
+}
+
+impl BlsVerifier for TestBls {
+    fn verify(pub_key: &[u8], signature: &[u8], message: &[u8]) -> bool {
+        test_bls_sign(pub_key, message) == signature
+    }
+
+    fn aggregate_verify(pub_keys: &[&[u8]], messages: &[&[u8]], signature: &[u8]) -> bool {
+        let signatures: Vec<Vec<u8>> = pub_keys
+            .iter()
+            .zip(messages)
+            .map(|(key, message)| test_bls_sign(key, message))
+            .collect();
+        !pub_keys.is_empty()
+            && pub_keys.len() == messages.len()
+            && test_bls_aggregate(&signatures) == signature
+    }
+}
+
+// The compressed G1 key of a real BLS12-381 `secret`, as `ArkworksBls` verifies it.
+pub fn ark_bls_key(secret: u64) -> Vec<u8> {
+    serialize(
+        &G1Affine::prime_subgroup_generator()
+            .mul(Fr::from(secret).into_repr())
+            .into_affine(),
+    )
+}
+
+// The signature of `message` by each secret, summed up into an aggregate.
+pub fn ark_bls_sign(signers: &[(u64, &[u8])]) -> Vec<u8> {
+    let aggregate = signers
+        .iter()
+        .fold(G2Projective::zero(), |aggregate, (secret, message)| {
+            aggregate
+                + bls::hash_to_g2(message)
+                    .unwrap()
+                    .mul(Fr::from(*secret).into_repr())
+        });
+    serialize(&aggregate.into_affine())
+}
+
+// Knows a square root of its second public input. The first one is the message hash, which
+// every circuit of a ZkSnark output takes.
+pub struct SquareRootCircuit {
+    pub message: Fr,
+    pub square: Fr,
+    pub root: Option<Fr>,
+}
+
+impl ConstraintSynthesizer<Fr> for SquareRootCircuit {
+    fn generate_constraints(self, cs: ConstraintSystemRef<Fr>) -> Result<(), SynthesisError> {
+        cs.new_input_variable(|| Ok(self.message))?;
+        let square = cs.new_input_variable(|| Ok(self.square))?;
+        let root =
+            cs.new_witness_variable(|| self.root.ok_or(SynthesisError::AssignmentMissing))?;
+        cs.enforce_constraint(lc!() + root, lc!() + root, lc!() + square)
+    }
+}
+
+pub fn serialize(item: &impl CanonicalSerialize) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    item.serialize(&mut bytes).unwrap();
+    bytes
+}
+
+// The proving key of the square root circuit, and the serialized verifying key.
+pub fn zk_setup(seed: u64) -> (ProvingKey<Bls12_381>, Vec<u8>) {
+    let circuit = SquareRootCircuit {
+        message: Fr::from(0u64),
+        square: Fr::from(0u64),
+        root: None,
+    };
+    let proving_key =
+        generate_random_parameters::<Bls12_381, _, _>(circuit, &mut StdRng::seed_from_u64(seed))
+            .unwrap();
+    let verifying_key = serialize(&proving_key.vk);
+    (proving_key, verifying_key)
+}
+
+// The public inputs of the square root circuit for the square of `root`.
+pub fn zk_square(root: u64) -> Vec<Vec<u8>> {
+    let root = Fr::from(root);
+    vec![serialize(&(root * root))]
+}
+
+// Proves knowing the square root `root` of its square, for the signature message `message`.
+pub fn zk_prove(proving_key: &ProvingKey<Bls12_381>, root: u64, message: &[u8]) -> ZkProof {
+    let circuit = SquareRootCircuit {
+        message: zk::message_input(message),
+        square: Fr::from(root) * Fr::from(root),
+        root: Some(Fr::from(root)),
+    };
+    let proof = create_random_proof(circuit, proving_key, &mut test_rng()).unwrap();
+
+    ZkProof {
+        verifying_key: serialize(&proving_key.vk),
+        proof: serialize(&proof),
+        public_inputs: zk_square(root),
+    }
+}
+
+fn is_revoked(pub_key: &H256) -> bool {
+    REVOKED_KEYS.with(|keys| keys.borrow().contains(pub_key))
+}
+
+fn bls_supported() -> bool {
+    BLS_SUPPORTED.with(|supported| *supported.borrow())
+}
+
+// Refuses the signatures of revoked keys, and leaves the rest to the default verifier, with
+// `TestBls` unless BLS support is turned off.
+pub struct RevocationVerifier;
+
+impl SignatureVerifier for RevocationVerifier {
+    fn verify(
+        utxo: &TransactionOutput,
+        pub_key: &H256,
+        signature: &[u8],
+        message: &[u8],
+    ) -> Result<(), SignatureError> {
+        if is_revoked(pub_key) {
+            return Err(SignatureError::Invalid);
+        }
+        if bls_supported() {
+            DefaultVerifier::<TestBls>::verify(utxo, pub_key, signature, message)
+        } else {
+            DefaultVerifier::<()>::verify(utxo, pub_key, signature, message)
+        }
+    }
+
+    fn verify_bls_aggregate(
+        pub_keys: &[&[u8]],
+        messages: &[&[u8]],
+        signature: &[u8],
+    ) -> Result<(), SignatureError> {
+        if pub_keys.iter().any(|key| is_revoked(&bls_key_hash(key))) {
+            return Err(SignatureError::Invalid);
+        }
+        if bls_supported() {
+            DefaultVerifier::<TestBls>::verify_bls_aggregate(pub_keys, messages, signature)
+        } else {
+            DefaultVerifier::<()>::verify_bls_aggregate(pub_keys, messages, signature)
+        }
+    }
+
+    fn supports_bls() -> bool {
+        bls_supported()
+    }
+}
+
 impl pallet_utxo::Config for Test {
     type Event = Event;
     type Call = Call;
     type WeightInfo = crate::weights::WeightInfo<Test>;
+    type SignatureVerifier = RevocationVerifier;
+    type Time = Timestamp;
+    type MinimumFeePerByte = MinimumFeePerByte;
+    type MinimumFeePerInput = MinimumFeePerInput;
+    type MinimumFeePerOutput = MinimumFeePerOutput;
 
     fn authorities() -> Vec<H256> {
         Aura::authorities()
@@ -129,21 +378,7 @@ fn create_pub_key(keystore: &KeyStore, phrase: &str) -> Public {
 pub fn new_test_ext() -> TestExternalities {
     let keystore = KeyStore::new(); // a key storage to store new key pairs during testing
     let alice_pub_key = create_pub_key(&keystore, ALICE_PHRASE);
-
-    let mut t = frame_system::GenesisConfig::default()
-        .build_storage::<Test>()
-        .unwrap();
-
-    pallet_utxo::GenesisConfig::<Test> {
-        genesis_utxos: vec![TransactionOutput::new(100, H256::from(alice_pub_key))],
-        _marker: Default::default(),
-    }
-    .assimilate_storage(&mut t)
-    .unwrap();
-
-    let mut ext = TestExternalities::from(t);
-    ext.register_extension(KeystoreExt(std::sync::Arc::new(keystore)));
-    ext
+    test_ext(keystore, alice_pub_key)
 }
 
 pub fn new_test_ext_and_keys() -> (TestExternalities, Public, Public) {
@@ -154,10 +389,19 @@ pub fn new_test_ext_and_keys() -> (TestExternalities, Public, Public) {
     let keystore = KeyStore::new();
     let alice_pub_key = create_pub_key(&keystore, ALICE_PHRASE);
     let karl_pub_key = create_pub_key(&keystore, KARL_PHRASE);
+    (
+        test_ext(keystore, alice_pub_key),
+        alice_pub_key,
+        karl_pub_key,
+    )
+}
 
+// The externalities every test runs in, where alice holds the genesis utxo.
+fn test_ext(keystore: KeyStore, alice_pub_key: Public) -> TestExternalities {
     let mut t = frame_system::GenesisConfig::default()
         .build_storage::<Test>()
         .unwrap();
+
     pallet_utxo::GenesisConfig::<Test> {
         genesis_utxos: vec![TransactionOutput::new(100, H256::from(alice_pub_key))],
         _marker: Default::default(),
@@ -165,7 +409,16 @@ pub fn new_test_ext_and_keys() -> (TestExternalities, Public, Public) {
     .assimilate_storage(&mut t)
     .unwrap();
 
+    // alice is also the only block author, so she collects the rewards
+    pallet_aura::GenesisConfig::<Test> {
+        authorities: vec![AuraId::from(alice_pub_key)],
+    }
+    .assimilate_storage(&mut t)
+    .unwrap();
+
     let mut ext = TestExternalities::from(t);
     ext.register_extension(KeystoreExt(std::sync::Arc::new(keystore)));
-    (ext, alice_pub_key, karl_pub_key)
+    // batch verification runs on the task executor
+    ext.register_extension(TaskExecutorExt::new(TaskExecutor::new()));
+    ext
 }
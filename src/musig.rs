@@ -0,0 +1,332 @@
+// Copyright (c) 2021 RBB S.r.l
+// opensource@mintlayer.org
+// SPDX-License-Identifier: MIT
+// Licensed under the MIT License;
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://spdx.org/licenses/MIT
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// Author(s): C. Yap
+
+//! Aggregated sr25519 keys and signatures with MuSig2, for off-chain signers.
+//!
+//! The signers of a federation aggregate their keys into one sr25519 key, which a
+//! `TransactionOutput::new` output is locked to like any other key. They then sign together in
+//! the two rounds of MuSig2, each on their own machine, and the result is a plain sr25519
+//! signature that the pallet's Schnorr verification accepts. On chain, nothing tells the
+//! output apart from a single signer's.
+//!
+//! 1. Every signer draws its nonces for the message with `commit`, and sends the
+//!    `PublicNonce` to the others. The `SecretNonce` never leaves the signer.
+//! 2. Once it has every public nonce, each signer signs with `sign_partial`, and sends the
+//!    `PartialSignature` on. Anyone holding all of them puts the signature together with
+//!    `aggregate_signature`, and `verify_partial` finds a signer who sent a bad one.
+//!
+//! MuSig2 takes every signer: an m-of-n federation locks its funds to a `Destination::Multisig`
+//! or to a taproot script path instead. `sign` runs both rounds in one place, which suits tests
+//! and signers sharing a process.
+
+use curve25519_dalek::{
+    constants::RISTRETTO_BASEPOINT_TABLE,
+    ristretto::{CompressedRistretto, RistrettoPoint},
+    scalar::Scalar,
+};
+use schnorrkel::{context::SigningTranscript, signing_context, Keypair, PublicKey};
+use sp_core::{H256, H512};
+
+/// The signing context of sr25519 signatures in substrate, which the pallet verifies.
+pub const SIGNING_CONTEXT: &[u8] = b"substrate";
+
+/// What the signers sign for `message`, as returned by `signature_message`.
+pub fn signing_transcript(message: &[u8]) -> impl SigningTranscript + Clone {
+    signing_context(SIGNING_CONTEXT).bytes(message)
+}
+
+// Hashes `items` to a scalar, apart from any other use of the hash by its `label`.
+fn hash_to_scalar(label: &'static [u8], items: &[&[u8]]) -> Scalar {
+    let mut transcript = signing_context(b"MuSig2").bytes(label);
+    for item in items {
+        transcript.commit_bytes(b"item", item);
+    }
+    transcript.challenge_scalar(b"scalar")
+}
+
+// The challenge of an sr25519 signature of `message` with the nonce `r`, as schnorrkel verifies
+// it.
+fn challenge(public_key: &PublicKey, r: &CompressedRistretto, message: &[u8]) -> Scalar {
+    let mut transcript = signing_transcript(message);
+    transcript.proto_name(b"Schnorr-sig");
+    transcript.commit_point(b"sign:pk", public_key.as_compressed());
+    transcript.commit_point(b"sign:R", r);
+    transcript.challenge_scalar(b"sign:c")
+}
+
+/// The keys of the signers, aggregated into the key their utxos are locked to.
+pub struct KeyAggregate {
+    // each key, in order, with the coefficient it is aggregated with
+    keys: Vec<(PublicKey, Scalar)>,
+    public_key: PublicKey,
+}
+
+impl KeyAggregate {
+    /// Aggregates the sr25519 `keys`, whatever their order. `None` if a key is not valid, or
+    /// is there twice.
+    pub fn new(keys: &[H256]) -> Option<Self> {
+        let mut keys = keys.to_vec();
+        keys.sort();
+        let count = keys.len();
+        keys.dedup();
+        if keys.is_empty() || keys.len() != count {
+            return None;
+        }
+
+        let all_keys = keys.iter().map(H256::as_bytes).collect::<Vec<_>>().concat();
+        let keys = keys
+            .iter()
+            .map(|key| {
+                let public_key = PublicKey::from_bytes(key.as_bytes()).ok()?;
+                let coefficient =
+                    hash_to_scalar(b"key coefficient", &[&all_keys[..], key.as_bytes()]);
+                Some((public_key, coefficient))
+            })
+            .collect::<Option<Vec<_>>>()?;
+        let public_key = PublicKey::from_point(
+            keys.iter()
+                .map(|(key, coefficient)| coefficient * key.as_point())
+                .sum(),
+        );
+        Some(KeyAggregate { keys, public_key })
+    }
+
+    /// The aggregate key.
+    pub fn public_key(&self) -> H256 {
+        H256(self.public_key.to_bytes())
+    }
+
+    fn coefficient(&self, key: &PublicKey) -> Option<Scalar> {
+        self.keys
+            .iter()
+            .find(|(other, _)| other == key)
+            .map(|(_, coefficient)| *coefficient)
+    }
+}
+
+/// The aggregate of the sr25519 `keys`, whatever their order. `None` if a key is not valid, or
+/// is there twice.
+pub fn aggregate_key(keys: &[H256]) -> Option<H256> {
+    KeyAggregate::new(keys).map(|keys| keys.public_key())
+}
+
+/// The two nonces a signer commits to in the first round, which it sends to the others.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct PublicNonce([RistrettoPoint; 2]);
+
+impl PublicNonce {
+    pub fn to_bytes(&self) -> [u8; 64] {
+        let mut bytes = [0; 64];
+        bytes[..32].copy_from_slice(self.0[0].compress().as_bytes());
+        bytes[32..].copy_from_slice(self.0[1].compress().as_bytes());
+        bytes
+    }
+
+    /// The nonces sent as `bytes`. `None` unless they are two valid points.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() != 64 {
+            return None;
+        }
+        let point = |bytes: &[u8]| CompressedRistretto::from_slice(bytes).decompress();
+        Some(PublicNonce([point(&bytes[..32])?, point(&bytes[32..])?]))
+    }
+}
+
+/// The secrets behind a signer's `PublicNonce`. They are used up by `sign_partial`: signing
+/// twice with the same nonces would give the secret key away.
+pub struct SecretNonce {
+    nonces: [Scalar; 2],
+    public: PublicNonce,
+}
+
+impl SecretNonce {
+    /// What the signer sends to the others.
+    pub fn public(&self) -> PublicNonce {
+        self.public
+    }
+}
+
+/// A signer's share of the signature, which it sends on in the second round.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct PartialSignature(Scalar);
+
+impl PartialSignature {
+    pub fn to_bytes(&self) -> [u8; 32] {
+        self.0.to_bytes()
+    }
+
+    /// The share sent as `bytes`. `None` unless they are a canonical scalar.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let mut scalar = [0; 32];
+        if bytes.len() != 32 {
+            return None;
+        }
+        scalar.copy_from_slice(bytes);
+        Scalar::from_canonical_bytes(scalar).map(PartialSignature)
+    }
+}
+
+/// Round 1: draws the nonces of `signer` for signing `message`. They are random, so that a
+/// signer asked to sign the same message twice does not reuse them.
+pub fn commit(signer: &Keypair, message: &[u8]) -> SecretNonce {
+    let transcript = signing_transcript(message);
+    let secret = signer.secret.to_bytes();
+    let nonces = [
+        transcript.witness_scalar(b"MuSig2 nonce 1", &[&secret[..]]),
+        transcript.witness_scalar(b"MuSig2 nonce 2", &[&secret[..]]),
+    ];
+    let public = PublicNonce([
+        &nonces[0] * &RISTRETTO_BASEPOINT_TABLE,
+        &nonces[1] * &RISTRETTO_BASEPOINT_TABLE,
+    ]);
+    SecretNonce { nonces, public }
+}
+
+// What all signers work out alike from the public nonces of every one of them.
+struct Session {
+    // the factor of each signer's second nonce
+    nonce_coefficient: Scalar,
+    nonce: CompressedRistretto,
+    challenge: Scalar,
+}
+
+impl Session {
+    fn new(keys: &KeyAggregate, nonces: &[PublicNonce], message: &[u8]) -> Option<Self> {
+        if nonces.len() != keys.keys.len() {
+            return None;
+        }
+        let first: RistrettoPoint = nonces.iter().map(|nonce| nonce.0[0]).sum();
+        let second: RistrettoPoint = nonces.iter().map(|nonce| nonce.0[1]).sum();
+
+        let nonce_coefficient = hash_to_scalar(
+            b"nonce coefficient",
+            &[
+                keys.public_key.as_compressed().as_bytes(),
+                first.compress().as_bytes(),
+                second.compress().as_bytes(),
+                message,
+            ],
+        );
+        let nonce = (first + nonce_coefficient * second).compress();
+        let challenge = challenge(&keys.public_key, &nonce, message);
+        Some(Session {
+            nonce_coefficient,
+            nonce,
+            challenge,
+        })
+    }
+}
+
+/// Round 2: signs `message` with `signer`, one of `keys`, once it has the public `nonces` of
+/// every signer, its own included, in any order. `None` if the signer or its nonce is not
+/// among them.
+pub fn sign_partial(
+    signer: &Keypair,
+    nonce: SecretNonce,
+    keys: &KeyAggregate,
+    nonces: &[PublicNonce],
+    message: &[u8],
+) -> Option<PartialSignature> {
+    let coefficient = keys.coefficient(&signer.public)?;
+    if !nonces.contains(&nonce.public) {
+        return None;
+    }
+    let session = Session::new(keys, nonces, message)?;
+
+    let mut secret = [0; 32];
+    secret.copy_from_slice(&signer.secret.to_bytes()[..32]);
+    let secret = Scalar::from_bytes_mod_order(secret);
+    Some(PartialSignature(
+        nonce.nonces[0]
+            + session.nonce_coefficient * nonce.nonces[1]
+            + session.challenge * coefficient * secret,
+    ))
+}
+
+/// Whether `partial` is the share of the signer of `key` with the public `nonce`, so that a
+/// signature failing to aggregate can be blamed on whoever sent a bad one.
+pub fn verify_partial(
+    keys: &KeyAggregate,
+    nonces: &[PublicNonce],
+    key: &H256,
+    nonce: &PublicNonce,
+    partial: &PartialSignature,
+    message: &[u8],
+) -> bool {
+    let key = match PublicKey::from_bytes(key.as_bytes()) {
+        Ok(key) => key,
+        Err(_) => return false,
+    };
+    let (coefficient, session) = match (keys.coefficient(&key), Session::new(keys, nonces, message))
+    {
+        (Some(coefficient), Some(session)) => (coefficient, session),
+        _ => return false,
+    };
+    &partial.0 * &RISTRETTO_BASEPOINT_TABLE
+        == nonce.0[0]
+            + session.nonce_coefficient * nonce.0[1]
+            + session.challenge * coefficient * key.as_point()
+}
+
+/// Puts together the sr25519 signature of `message` for the aggregate of `keys`, from the
+/// `partials` of every signer. `None` if it does not verify.
+pub fn aggregate_signature(
+    keys: &KeyAggregate,
+    nonces: &[PublicNonce],
+    partials: &[PartialSignature],
+    message: &[u8],
+) -> Option<H512> {
+    let session = Session::new(keys, nonces, message)?;
+    if partials.len() != keys.keys.len() {
+        return None;
+    }
+    let s: Scalar = partials.iter().map(|partial| partial.0).sum();
+    let nonce = session.nonce.decompress()?;
+    if &s * &RISTRETTO_BASEPOINT_TABLE != nonce + session.challenge * keys.public_key.as_point() {
+        return None;
+    }
+
+    // schnorrkel marks its signatures in the top bit, which a canonical scalar leaves free
+    let mut signature = [0; 64];
+    signature[..32].copy_from_slice(session.nonce.as_bytes());
+    signature[32..].copy_from_slice(s.as_bytes());
+    signature[63] |= 128;
+    Some(H512(signature))
+}
+
+/// Signs `message` with all of `signers`, for their `aggregate_key`, running both rounds in
+/// one place.
+pub fn sign(signers: &[Keypair], message: &[u8]) -> Option<H512> {
+    let keys: Vec<H256> = signers
+        .iter()
+        .map(|signer| H256(signer.public.to_bytes()))
+        .collect();
+    let keys = KeyAggregate::new(&keys)?;
+
+    let secret_nonces: Vec<SecretNonce> = signers
+        .iter()
+        .map(|signer| commit(signer, message))
+        .collect();
+    let nonces: Vec<PublicNonce> = secret_nonces.iter().map(SecretNonce::public).collect();
+    let partials = signers
+        .iter()
+        .zip(secret_nonces)
+        .map(|(signer, nonce)| sign_partial(signer, nonce, &keys, &nonces, message))
+        .collect::<Option<Vec<_>>>()?;
+
+    aggregate_signature(&keys, &nonces, &partials, message)
+}
@@ -0,0 +1,671 @@
+// Copyright (c) 2021 RBB S.r.l
+// opensource@mintlayer.org
+// SPDX-License-Identifier: MIT
+// Licensed under the MIT License;
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://spdx.org/licenses/MIT
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// Author(s): C. Yap
+
+//! A small stack machine for the spending conditions of `Destination::Script`.
+//!
+//! The witness of the input is the initial stack, bottom item first, and the locking script of
+//! the output runs on top of it. The input is authorised when the script ends with exactly one
+//! true item on the stack. Items are byte strings; numbers are little endian and unsigned, at
+//! most 8 bytes long; an item is true when it has a non-zero byte.
+
+#[cfg(feature = "std")]
+use serde::{Deserialize, Serialize};
+
+use crate::{SignatureError, Timelock, MAX_MULTISIG_KEYS};
+use codec::{Decode, Encode};
+use frame_support::{
+    ensure,
+    sp_io::hashing::{blake2_256, sha2_256},
+    RuntimeDebug,
+};
+use sp_core::{
+    sp_std::{convert::TryFrom, vec, vec::Vec},
+    H256,
+};
+
+/// Most operations a script may hold.
+pub const MAX_SCRIPT_OPS: usize = 201;
+
+/// Most items the stack may hold.
+pub const MAX_STACK_ITEMS: usize = 1000;
+
+/// Most bytes a stack item may hold.
+pub const MAX_ITEM_SIZE: usize = 520;
+
+/// Most signature checks a script may run, counting every key of a multisig. Apart from that,
+/// a script verifies at most as many signatures as its witness holds items, which are what a
+/// spend is charged for.
+pub const MAX_SIG_CHECKS: usize = 80;
+
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+#[derive(Clone, Encode, Decode, Eq, PartialEq, PartialOrd, Ord, RuntimeDebug, Hash)]
+pub enum Op {
+    /// Pushes the bytes.
+    Push(Vec<u8>),
+    /// Duplicates the top item.
+    Dup,
+    /// Removes the top item.
+    Drop,
+    /// Swaps the two top items.
+    Swap,
+    /// Replaces the two top items with whether they are equal.
+    Equal,
+    /// `Equal` then `Verify`.
+    EqualVerify,
+    /// Removes the top item, and fails unless it is true.
+    Verify,
+    /// Replaces the top item with its negation.
+    Not,
+    /// Replaces the two top items with whether both are true.
+    BoolAnd,
+    /// Replaces the two top items with whether any of them is true.
+    BoolOr,
+    /// Runs the next operations up to the matching `Else` or `EndIf` if the top item,
+    /// which must be empty or `[1]`, is true.
+    If,
+    /// Like `If`, on a false item.
+    NotIf,
+    /// Switches between the branches of the current `If`.
+    Else,
+    /// Ends the current `If`.
+    EndIf,
+    /// Fails, which makes the output unspendable.
+    Return,
+    /// Replaces the top item with its sha256 hash.
+    Sha256,
+    /// Replaces the top item with its blake2 256 bits hash.
+    Blake2_256,
+    /// Replaces a key on top of a signature with whether the signature of the input is valid
+    /// for that key. An invalid signature must be empty, otherwise the script fails.
+    CheckSig,
+    /// `CheckSig` then `Verify`.
+    CheckSigVerify,
+    /// Takes, from the top, the number of keys `n`, the `n` keys, the number of signatures `m`
+    /// and `n` signatures, one for each key in the same order, and pushes whether at least `m`
+    /// of them are valid. Keys that do not sign get an empty signature, and any other signature
+    /// must be valid, otherwise the script fails. If the check fails, all signatures must be
+    /// empty.
+    CheckMultisig,
+    /// `CheckMultisig` then `Verify`.
+    CheckMultisigVerify,
+    /// Locks the input until the block number on top of the stack, which is left in place.
+    CheckLockTimeVerify,
+    /// Locks the input for the number of blocks on top of the stack after the creation of the
+    /// spent output. The number is left in place.
+    CheckSequenceVerify,
+    /// Locks the input until the chain's time, in seconds since the unix epoch, on top of the
+    /// stack, which is left in place.
+    CheckLockTimestampVerify,
+}
+
+pub type Script = Vec<Op>;
+
+/// Why a script does not authorise its input.
+#[derive(Clone, Copy, Eq, PartialEq, RuntimeDebug)]
+pub enum ScriptError {
+    /// The script holds more than `MAX_SCRIPT_OPS` operations.
+    TooManyOps,
+    /// The stack holds more than `MAX_STACK_ITEMS` items.
+    StackOverflow,
+    /// An item is longer than `MAX_ITEM_SIZE` bytes.
+    ItemTooLarge,
+    /// The script runs more than `MAX_SIG_CHECKS` signature checks, or verifies more signatures
+    /// than its witness holds items.
+    TooManySigChecks,
+    /// An operation needs more items than the stack holds.
+    StackUnderflow,
+    /// An item is not a number, or not one the operation accepts.
+    InvalidNumber,
+    /// The condition of an `If` is neither empty nor `[1]`.
+    MinimalIf,
+    /// An `Else` or `EndIf` without `If`, or an `If` without `EndIf`.
+    UnbalancedConditional,
+    /// A `Verify` or `Return` failed.
+    VerifyFailed,
+    /// A failed signature check was given a non-empty signature.
+    NullFail,
+    /// A key uses a signature method the verifier does not know.
+    UnsupportedSignatureMethod,
+    /// The script did not end with exactly one true item.
+    EvalFalse,
+}
+
+impl Op {
+    /// Pushes `number`, encoded in as few bytes as possible.
+    pub fn number(number: u64) -> Op {
+        let bytes = number.to_le_bytes();
+        let len = 8 - (number.leading_zeros() / 8) as usize;
+        Op::Push(bytes[..len].to_vec())
+    }
+
+    /// Pushes `key`.
+    pub fn key(key: H256) -> Op {
+        Op::Push(key.as_bytes().to_vec())
+    }
+}
+
+fn to_bool(item: &[u8]) -> bool {
+    item.iter().any(|byte| *byte != 0)
+}
+
+fn from_bool(value: bool) -> Vec<u8> {
+    if value {
+        vec![1]
+    } else {
+        vec![]
+    }
+}
+
+fn to_number(item: &[u8]) -> Result<u64, ScriptError> {
+    ensure!(item.len() <= 8, ScriptError::InvalidNumber);
+    let mut bytes = [0u8; 8];
+    bytes[..item.len()].copy_from_slice(item);
+    Ok(u64::from_le_bytes(bytes))
+}
+
+struct Machine<F> {
+    stack: Vec<Vec<u8>>,
+    sig_checks: usize,
+    // signatures still to be verified, one for each item of the witness
+    verifications_left: usize,
+    locks: Vec<Timelock>,
+    check_sig: F,
+}
+
+impl<F> Machine<F>
+where
+    F: FnMut(&H256, &[u8]) -> Result<(), SignatureError>,
+{
+    fn pop(&mut self) -> Result<Vec<u8>, ScriptError> {
+        self.stack.pop().ok_or(ScriptError::StackUnderflow)
+    }
+
+    fn top(&self) -> Result<&Vec<u8>, ScriptError> {
+        self.stack.last().ok_or(ScriptError::StackUnderflow)
+    }
+
+    fn push(&mut self, item: Vec<u8>) -> Result<(), ScriptError> {
+        ensure!(item.len() <= MAX_ITEM_SIZE, ScriptError::ItemTooLarge);
+        self.stack.push(item);
+        Ok(())
+    }
+
+    // Pops `count` items, returned in stack order, bottom first.
+    fn pop_many(&mut self, count: usize) -> Result<Vec<Vec<u8>>, ScriptError> {
+        let len = self.stack.len();
+        ensure!(count <= len, ScriptError::StackUnderflow);
+        Ok(self.stack.split_off(len - count))
+    }
+
+    fn count_sig_checks(&mut self, count: usize) -> Result<(), ScriptError> {
+        self.sig_checks = self.sig_checks.saturating_add(count);
+        ensure!(
+            self.sig_checks <= MAX_SIG_CHECKS,
+            ScriptError::TooManySigChecks
+        );
+        Ok(())
+    }
+
+    // Whether `signature` is valid for `key`. An empty signature, or a key of another size
+    // than 32 bytes, is never valid.
+    fn signature_valid(&mut self, key: &[u8], signature: &[u8]) -> Result<bool, ScriptError> {
+        let key = match <[u8; 32]>::try_from(key) {
+            Ok(key) if !signature.is_empty() => H256::from(key),
+            _ => return Ok(false),
+        };
+        self.verifications_left = self
+            .verifications_left
+            .checked_sub(1)
+            .ok_or(ScriptError::TooManySigChecks)?;
+        match (self.check_sig)(&key, signature) {
+            Ok(()) => Ok(true),
+            Err(SignatureError::Invalid) => Ok(false),
+            Err(SignatureError::UnsupportedMethod) => Err(ScriptError::UnsupportedSignatureMethod),
+        }
+    }
+
+    fn check_sig(&mut self) -> Result<bool, ScriptError> {
+        let key = self.pop()?;
+        let signature = self.pop()?;
+        self.count_sig_checks(1)?;
+
+        let valid = self.signature_valid(&key, &signature)?;
+        ensure!(valid || signature.is_empty(), ScriptError::NullFail);
+        Ok(valid)
+    }
+
+    fn check_multisig(&mut self) -> Result<bool, ScriptError> {
+        let key_count = to_number(&self.pop()?)? as usize;
+        ensure!(key_count <= MAX_MULTISIG_KEYS, ScriptError::InvalidNumber);
+        self.count_sig_checks(key_count)?;
+        let keys = self.pop_many(key_count)?;
+
+        let sig_count = to_number(&self.pop()?)? as usize;
+        ensure!(sig_count <= key_count, ScriptError::InvalidNumber);
+        let signatures = self.pop_many(key_count)?;
+
+        // Each signature sits at the position of its key, so that only the signatures given are
+        // checked, and all of them must be valid. Trying a signature against several keys would
+        // not work with batch verification, where every check counts until the batch ends.
+        let mut signed = 0;
+        for (key, signature) in keys.iter().zip(&signatures) {
+            if !signature.is_empty() {
+                ensure!(self.signature_valid(key, signature)?, ScriptError::NullFail);
+                signed += 1;
+            }
+        }
+
+        let valid = signed >= sig_count;
+        ensure!(valid || signed == 0, ScriptError::NullFail);
+        Ok(valid)
+    }
+
+    fn verify(&mut self, valid: bool) -> Result<(), ScriptError> {
+        ensure!(valid, ScriptError::VerifyFailed);
+        Ok(())
+    }
+
+    fn step(&mut self, op: &Op) -> Result<(), ScriptError> {
+        match op {
+            Op::Push(data) => self.push(data.clone())?,
+            Op::Dup => {
+                let top = self.top()?.clone();
+                self.push(top)?;
+            }
+            Op::Drop => {
+                self.pop()?;
+            }
+            Op::Swap => {
+                let len = self.stack.len();
+                ensure!(len >= 2, ScriptError::StackUnderflow);
+                self.stack.swap(len - 1, len - 2);
+            }
+            Op::Equal | Op::EqualVerify => {
+                let equal = self.pop()? == self.pop()?;
+                if let Op::Equal = op {
+                    self.push(from_bool(equal))?;
+                } else {
+                    self.verify(equal)?;
+                }
+            }
+            Op::Verify => {
+                let top = self.pop()?;
+                self.verify(to_bool(&top))?;
+            }
+            Op::Not => {
+                let top = self.pop()?;
+                self.push(from_bool(!to_bool(&top)))?;
+            }
+            Op::BoolAnd | Op::BoolOr => {
+                let a = to_bool(&self.pop()?);
+                let b = to_bool(&self.pop()?);
+                let result = if let Op::BoolAnd = op { a && b } else { a || b };
+                self.push(from_bool(result))?;
+            }
+            Op::Return => self.verify(false)?,
+            Op::Sha256 => {
+                let top = self.pop()?;
+                self.push(sha2_256(&top).to_vec())?;
+            }
+            Op::Blake2_256 => {
+                let top = self.pop()?;
+                self.push(blake2_256(&top).to_vec())?;
+            }
+            Op::CheckSig => {
+                let valid = self.check_sig()?;
+                self.push(from_bool(valid))?;
+            }
+            Op::CheckSigVerify => {
+                let valid = self.check_sig()?;
+                self.verify(valid)?;
+            }
+            Op::CheckMultisig => {
+                let valid = self.check_multisig()?;
+                self.push(from_bool(valid))?;
+            }
+            Op::CheckMultisigVerify => {
+                let valid = self.check_multisig()?;
+                self.verify(valid)?;
+            }
+            Op::CheckLockTimeVerify => {
+                let block = to_number(self.top()?)?;
+                self.locks.push(Timelock::UntilBlock(block));
+            }
+            Op::CheckSequenceVerify => {
+                let blocks = to_number(self.top()?)?;
+                self.locks.push(Timelock::AfterBlocks(blocks));
+            }
+            Op::CheckLockTimestampVerify => {
+                let time = to_number(self.top()?)?;
+                self.locks.push(Timelock::UntilTime(time));
+            }
+            Op::If | Op::NotIf | Op::Else | Op::EndIf => {
+                unreachable!("conditionals are handled by `eval`")
+            }
+        }
+        ensure!(
+            self.stack.len() <= MAX_STACK_ITEMS,
+            ScriptError::StackOverflow
+        );
+        Ok(())
+    }
+}
+
+/// Runs `script` on the `witness` stack. `check_sig` verifies a signature of the input for a
+/// key. On success, returns the timelocks the script put on the input: a script cannot see
+/// the chain, so it is up to the caller to hold the input until they expire.
+pub fn eval<F>(
+    script: &[Op],
+    witness: &[Vec<u8>],
+    check_sig: F,
+) -> Result<Vec<Timelock>, ScriptError>
+where
+    F: FnMut(&H256, &[u8]) -> Result<(), SignatureError>,
+{
+    ensure!(script.len() <= MAX_SCRIPT_OPS, ScriptError::TooManyOps);
+    ensure!(witness.len() <= MAX_STACK_ITEMS, ScriptError::StackOverflow);
+    ensure!(
+        witness.iter().all(|item| item.len() <= MAX_ITEM_SIZE),
+        ScriptError::ItemTooLarge
+    );
+
+    let mut machine = Machine {
+        stack: witness.to_vec(),
+        sig_checks: 0,
+        verifications_left: witness.len(),
+        locks: Vec::new(),
+        check_sig,
+    };
+    // whether each enclosing `If` runs its current branch
+    let mut branches: Vec<bool> = Vec::new();
+
+    for op in script {
+        let running = branches.iter().all(|branch| *branch);
+        match op {
+            Op::If | Op::NotIf => {
+                let mut branch = false;
+                if running {
+                    branch = match machine.pop()?.as_slice() {
+                        [] => false,
+                        [1] => true,
+                        _ => return Err(ScriptError::MinimalIf),
+                    };
+                    if let Op::NotIf = op {
+                        branch = !branch;
+                    }
+                }
+                branches.push(branch);
+            }
+            Op::Else => {
+                let branch = branches
+                    .last_mut()
+                    .ok_or(ScriptError::UnbalancedConditional)?;
+                *branch = !*branch;
+            }
+            Op::EndIf => {
+                branches.pop().ok_or(ScriptError::UnbalancedConditional)?;
+            }
+            _ if running => machine.step(op)?,
+            _ => {}
+        }
+    }
+
+    ensure!(branches.is_empty(), ScriptError::UnbalancedConditional);
+    match machine.stack.as_slice() {
+        [top] if to_bool(top) => Ok(machine.locks),
+        _ => Err(ScriptError::EvalFalse),
+    }
+}
+
+/// Whether `script` fits the limits of the interpreter, so that an output locked to it is not
+/// unspendable from the start.
+pub fn is_valid(script: &[Op]) -> bool {
+    script.len() <= MAX_SCRIPT_OPS
+        && script.iter().all(|op| match op {
+            Op::Push(data) => data.len() <= MAX_ITEM_SIZE,
+            _ => true,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: H256 = H256::repeat_byte(7);
+    const OTHER_KEY: H256 = H256::repeat_byte(8);
+
+    // A signature is valid when it is the key itself.
+    fn check_sig(key: &H256, signature: &[u8]) -> Result<(), SignatureError> {
+        if key.as_bytes() == signature {
+            Ok(())
+        } else {
+            Err(SignatureError::Invalid)
+        }
+    }
+
+    fn run(script: &[Op], witness: &[Vec<u8>]) -> Result<Vec<Timelock>, ScriptError> {
+        eval(script, witness, check_sig)
+    }
+
+    fn sig(key: H256) -> Vec<u8> {
+        key.as_bytes().to_vec()
+    }
+
+    #[test]
+    fn numbers() {
+        assert_eq!(Op::number(0), Op::Push(vec![]));
+        assert_eq!(Op::number(0x1234), Op::Push(vec![0x34, 0x12]));
+        assert_eq!(to_number(&[0x34, 0x12]), Ok(0x1234));
+        assert_eq!(to_number(&[1; 9]), Err(ScriptError::InvalidNumber));
+    }
+
+    #[test]
+    fn check_sig_script() {
+        let script = vec![Op::key(KEY), Op::CheckSig];
+        assert_eq!(run(&script, &[sig(KEY)]), Ok(vec![]));
+        // an empty signature fails cleanly, another one breaks the script
+        assert_eq!(run(&script, &[vec![]]), Err(ScriptError::EvalFalse));
+        assert_eq!(run(&script, &[sig(OTHER_KEY)]), Err(ScriptError::NullFail));
+        assert_eq!(run(&script, &[]), Err(ScriptError::StackUnderflow));
+        // the stack must be left clean
+        assert_eq!(
+            run(&script, &[vec![1], sig(KEY)]),
+            Err(ScriptError::EvalFalse)
+        );
+    }
+
+    #[test]
+    fn multisig_script() {
+        let third_key = H256::repeat_byte(9);
+        let script = vec![
+            Op::number(2),
+            Op::key(KEY),
+            Op::key(OTHER_KEY),
+            Op::key(third_key),
+            Op::number(3),
+            Op::CheckMultisig,
+        ];
+
+        assert_eq!(
+            run(&script, &[sig(KEY), vec![], sig(third_key)]),
+            Ok(vec![])
+        );
+        assert_eq!(
+            run(&script, &[sig(KEY), sig(OTHER_KEY), sig(third_key)]),
+            Ok(vec![])
+        );
+        // signatures sit at the position of their keys
+        assert_eq!(
+            run(&script, &[sig(third_key), vec![], sig(KEY)]),
+            Err(ScriptError::NullFail)
+        );
+        // one key cannot sign twice
+        assert_eq!(
+            run(&script, &[sig(KEY), sig(KEY), vec![]]),
+            Err(ScriptError::NullFail)
+        );
+        // too few signatures fail cleanly only when all are empty
+        assert_eq!(
+            run(&script, &[vec![], vec![], vec![]]),
+            Err(ScriptError::EvalFalse)
+        );
+        assert_eq!(
+            run(&script, &[sig(KEY), vec![], vec![]]),
+            Err(ScriptError::NullFail)
+        );
+        assert_eq!(
+            run(&script, &[sig(KEY), sig(third_key)]),
+            Err(ScriptError::StackUnderflow)
+        );
+    }
+
+    #[test]
+    fn hash_lock_with_timeout() {
+        let preimage = b"preimage".to_vec();
+        // the key holder reveals the preimage, or takes the output back from block 100
+        let script = vec![
+            Op::If,
+            Op::Sha256,
+            Op::Push(sha2_256(&preimage).to_vec()),
+            Op::EqualVerify,
+            Op::key(KEY),
+            Op::Else,
+            Op::number(100),
+            Op::CheckLockTimeVerify,
+            Op::Drop,
+            Op::key(OTHER_KEY),
+            Op::EndIf,
+            Op::CheckSig,
+        ];
+
+        assert_eq!(
+            run(&script, &[sig(KEY), preimage.clone(), vec![1]]),
+            Ok(vec![])
+        );
+        assert_eq!(
+            run(&script, &[sig(KEY), b"guess".to_vec(), vec![1]]),
+            Err(ScriptError::VerifyFailed)
+        );
+        assert_eq!(
+            run(&script, &[sig(OTHER_KEY), vec![]]),
+            Ok(vec![Timelock::UntilBlock(100)])
+        );
+        // the condition must be minimal
+        assert_eq!(
+            run(&script, &[sig(OTHER_KEY), vec![0]]),
+            Err(ScriptError::MinimalIf)
+        );
+    }
+
+    #[test]
+    fn boolean_logic() {
+        let script = vec![Op::BoolOr, Op::Not, Op::Not];
+        assert_eq!(run(&script, &[vec![], vec![1]]), Ok(vec![]));
+        assert_eq!(run(&script, &[vec![], vec![]]), Err(ScriptError::EvalFalse));
+
+        let script = vec![Op::BoolAnd];
+        assert_eq!(run(&script, &[vec![1], vec![1]]), Ok(vec![]));
+        assert_eq!(
+            run(&script, &[vec![1], vec![]]),
+            Err(ScriptError::EvalFalse)
+        );
+        assert_eq!(
+            run(&[Op::Return], &[vec![1]]),
+            Err(ScriptError::VerifyFailed)
+        );
+    }
+
+    #[test]
+    fn relative_lock() {
+        let script = vec![Op::number(6), Op::CheckSequenceVerify];
+        assert_eq!(run(&script, &[]), Ok(vec![Timelock::AfterBlocks(6)]));
+    }
+
+    #[test]
+    fn time_lock() {
+        let script = vec![
+            Op::number(1_600_000_000),
+            Op::CheckLockTimestampVerify,
+            Op::Drop,
+            Op::key(KEY),
+            Op::CheckSig,
+        ];
+        assert_eq!(
+            run(&script, &[sig(KEY)]),
+            Ok(vec![Timelock::UntilTime(1_600_000_000)])
+        );
+    }
+
+    #[test]
+    fn unbalanced_conditionals() {
+        assert_eq!(
+            run(&[Op::If], &[vec![1]]),
+            Err(ScriptError::UnbalancedConditional)
+        );
+        assert_eq!(
+            run(&[Op::EndIf], &[vec![1]]),
+            Err(ScriptError::UnbalancedConditional)
+        );
+        // operations of a branch not taken are skipped, even invalid ones
+        let script = vec![Op::NotIf, Op::Return, Op::EndIf, Op::number(1)];
+        assert_eq!(run(&script, &[vec![1]]), Ok(vec![]));
+    }
+
+    #[test]
+    fn limits() {
+        let script = vec![Op::Dup; MAX_SCRIPT_OPS + 1];
+        assert_eq!(run(&script, &[vec![1]]), Err(ScriptError::TooManyOps));
+        assert!(!is_valid(&script));
+
+        let script = vec![Op::Push(vec![1; MAX_ITEM_SIZE + 1])];
+        assert_eq!(run(&script, &[]), Err(ScriptError::ItemTooLarge));
+        assert!(!is_valid(&script));
+
+        let witness = vec![vec![1]; MAX_STACK_ITEMS];
+        assert_eq!(run(&[Op::Dup], &witness), Err(ScriptError::StackOverflow));
+
+        // 5 multisig checks of 20 keys go over the signature check budget
+        let mut multisig = vec![Op::number(0)];
+        multisig.extend(vec![Op::key(KEY); MAX_MULTISIG_KEYS]);
+        multisig.extend(vec![
+            Op::number(MAX_MULTISIG_KEYS as u64),
+            Op::CheckMultisigVerify,
+        ]);
+        let script: Vec<Op> = multisig
+            .iter()
+            .cloned()
+            .cycle()
+            .take(multisig.len() * 5)
+            .collect();
+        let witness = vec![vec![]; 4 * MAX_MULTISIG_KEYS];
+        assert_eq!(run(&script, &witness), Err(ScriptError::TooManySigChecks));
+
+        // a signature of the witness is verified once, however often it is duplicated
+        let script = vec![
+            Op::Dup,
+            Op::key(KEY),
+            Op::CheckSigVerify,
+            Op::key(KEY),
+            Op::CheckSig,
+        ];
+        assert_eq!(
+            run(&script, &[sig(KEY)]),
+            Err(ScriptError::TooManySigChecks)
+        );
+        assert_eq!(run(&script[1..], &[sig(KEY), sig(KEY)]), Ok(vec![]));
+    }
+}
@@ -0,0 +1,173 @@
+// Copyright (c) 2021 RBB S.r.l
+// opensource@mintlayer.org
+// SPDX-License-Identifier: MIT
+// Licensed under the MIT License;
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://spdx.org/licenses/MIT
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// Author(s): C. Yap
+
+//! Key tweaks and Merkle trees of `Destination::Taproot`.
+//!
+//! A taproot output is locked to the sr25519 key `Q = P + t·G`, where `P` is the internal key
+//! and the tweak `t` commits to `P` and to the Merkle root of the alternative spending
+//! policies. The owner of `P` spends with a plain signature of `Q` (key path), while anyone
+//! satisfying one of the policies spends by revealing it with its Merkle path (script path).
+//! Until then, the output looks like any other single key.
+
+use crate::Destination;
+use codec::{Decode, Encode};
+use curve25519_dalek::{
+    constants::RISTRETTO_BASEPOINT_TABLE, ristretto::CompressedRistretto, scalar::Scalar,
+};
+use frame_support::{sp_io::hashing::blake2_256, RuntimeDebug};
+use sp_core::{sp_std::vec::Vec, H256};
+
+/// What the script path reveals, after the witness of the policy and the encoded policy.
+#[derive(Clone, Encode, Decode, Eq, PartialEq, RuntimeDebug)]
+pub struct ControlBlock {
+    /// The key `P` the output key was tweaked from.
+    pub internal_key: H256,
+    /// The sibling hashes from the leaf of the policy up to the Merkle root.
+    pub path: Vec<H256>,
+}
+
+fn tagged_hash(tag: &[u8], data: &[&[u8]]) -> H256 {
+    let mut preimage = tag.to_vec();
+    for item in data {
+        preimage.extend_from_slice(item);
+    }
+    H256(blake2_256(&preimage))
+}
+
+/// The leaf of the Merkle tree committing to `policy`.
+pub fn leaf_hash(policy: &Destination) -> H256 {
+    tagged_hash(b"TapLeaf", &[&policy.encode()])
+}
+
+/// The parent of two nodes. Children are sorted, so a path does not need their sides.
+pub fn branch_hash(a: &H256, b: &H256) -> H256 {
+    let (left, right) = if a <= b { (a, b) } else { (b, a) };
+    tagged_hash(b"TapBranch", &[left.as_bytes(), right.as_bytes()])
+}
+
+/// The root reached from `leaf` through the sibling hashes of `path`.
+pub fn merkle_root(leaf: H256, path: &[H256]) -> H256 {
+    path.iter()
+        .fold(leaf, |node, sibling| branch_hash(&node, sibling))
+}
+
+/// The tweak of `internal_key`. Without policies, it commits to the key alone.
+pub fn tweak(internal_key: &H256, merkle_root: Option<H256>) -> Scalar {
+    let hash = match merkle_root {
+        Some(root) => tagged_hash(b"TapTweak", &[internal_key.as_bytes(), root.as_bytes()]),
+        None => tagged_hash(b"TapTweak", &[internal_key.as_bytes()]),
+    };
+    Scalar::from_bytes_mod_order(hash.0)
+}
+
+/// The output key `Q = P + t·G`, or `None` if `internal_key` is not a Ristretto point.
+pub fn tweaked_key(internal_key: &H256, merkle_root: Option<H256>) -> Option<H256> {
+    let point = CompressedRistretto(internal_key.0).decompress()?;
+    let tweaked = point + &tweak(internal_key, merkle_root) * &RISTRETTO_BASEPOINT_TABLE;
+    Some(H256(tweaked.compress().to_bytes()))
+}
+
+/// Whether the control block proves that `policy` is committed to by `output_key`.
+pub fn verify_script_path(output_key: &H256, policy: &Destination, control: &ControlBlock) -> bool {
+    let root = merkle_root(leaf_hash(policy), &control.path);
+    tweaked_key(&control.internal_key, Some(root)).as_ref() == Some(output_key)
+}
+
+/// The secret key of the output key, to sign for the key path: the secret scalar of
+/// `secret_key` plus the tweak. The nonce seed is kept.
+#[cfg(feature = "std")]
+pub fn tweak_secret_key(
+    secret_key: &schnorrkel::SecretKey,
+    merkle_root: Option<H256>,
+) -> schnorrkel::SecretKey {
+    let internal_key = H256(secret_key.to_public().to_bytes());
+    let mut bytes = secret_key.to_bytes();
+
+    let mut scalar = [0u8; 32];
+    scalar.copy_from_slice(&bytes[..32]);
+    let tweaked = Scalar::from_bytes_mod_order(scalar) + tweak(&internal_key, merkle_root);
+    bytes[..32].copy_from_slice(tweaked.as_bytes());
+
+    schnorrkel::SecretKey::from_bytes(&bytes).expect("a reduced scalar is canonical; qed")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use schnorrkel::{ExpansionMode, MiniSecretKey};
+
+    fn keypair(seed: u8) -> schnorrkel::Keypair {
+        MiniSecretKey::from_bytes(&[seed; 32])
+            .unwrap()
+            .expand_to_keypair(ExpansionMode::Ed25519)
+    }
+
+    #[test]
+    fn merkle_paths() {
+        let leaves: Vec<H256> = (0..3u8)
+            .map(|byte| leaf_hash(&Destination::Pubkey(H256::repeat_byte(byte))))
+            .collect();
+        let left = branch_hash(&leaves[0], &leaves[1]);
+        let root = branch_hash(&left, &leaves[2]);
+
+        assert_eq!(merkle_root(leaves[0], &[leaves[1], leaves[2]]), root);
+        assert_eq!(merkle_root(leaves[1], &[leaves[0], leaves[2]]), root);
+        assert_eq!(merkle_root(leaves[2], &[left]), root);
+        assert_ne!(merkle_root(leaves[2], &[leaves[0]]), root);
+    }
+
+    #[test]
+    fn tweaked_secret_matches_tweaked_key() {
+        let keypair = keypair(1);
+        let internal_key = H256(keypair.public.to_bytes());
+
+        for root in [None, Some(H256::repeat_byte(9))].iter() {
+            let secret_key = tweak_secret_key(&keypair.secret, *root);
+            assert_eq!(
+                Some(H256(secret_key.to_public().to_bytes())),
+                tweaked_key(&internal_key, *root)
+            );
+        }
+        assert_ne!(
+            tweaked_key(&internal_key, None),
+            tweaked_key(&internal_key, Some(H256::zero()))
+        );
+    }
+
+    #[test]
+    fn script_path() {
+        let internal_key = H256(keypair(2).public.to_bytes());
+        let policy = Destination::Pubkey(H256::repeat_byte(3));
+        let other_policy = Destination::Pubkey(H256::repeat_byte(4));
+        let root = branch_hash(&leaf_hash(&policy), &leaf_hash(&other_policy));
+        let output_key = tweaked_key(&internal_key, Some(root)).unwrap();
+
+        let control = ControlBlock {
+            internal_key,
+            path: vec![leaf_hash(&other_policy)],
+        };
+        assert!(verify_script_path(&output_key, &policy, &control));
+        assert!(!verify_script_path(
+            &output_key,
+            &Destination::Pubkey(H256::repeat_byte(5)),
+            &control
+        ));
+
+        // not every 32 bytes are a point
+        assert_eq!(tweaked_key(&H256::repeat_byte(0xff), None), None);
+    }
+}
@@ -16,23 +16,61 @@
 // Author(s): C. Yap
 
 use crate::{
-    mock::*, RewardTotal, Transaction, TransactionInput, TransactionOutput, UtxoStore, Value,
+    bls::ArkworksBls,
+    bls_key_hash, ecdsa_key_hash, migrations, minimum_fee, mint_message, mint_nft_message,
+    mint_outpoint,
+    mock::*,
+    musig,
+    script::{Op, MAX_SCRIPT_OPS},
+    signature_message,
+    taproot::{self, ControlBlock},
+    BlsVerifier, Call as UtxoCall, DefaultVerifier, Destination, Error, NextTokenId, NftData, Nfts,
+    OutPoint, OutputHeader, Releases, RewardTotal, SigHash, SignatureError, SignatureMethod,
+    SignatureVerifier, StorageVersion, Timelock, TokenID, TokenRegistry, TokenSupply, TokenType,
+    Transaction, TransactionInput, TransactionOutput, UtxoCreation, UtxoStore, Value,
+    BLS_PUBLIC_KEY_SIZE, FIRST_ISSUED_TOKEN_ID, MAX_MULTISIG_KEYS, MAX_REWARD_TOKENS,
+    MAX_TIMELOCK_LONGEVITY, MAX_ZK_INPUTS,
 };
+use ark_bls12_381::{Bls12_381, G1Affine};
+use ark_ff::Zero;
+use ark_groth16::ProvingKey;
 use codec::Encode;
 use frame_support::{
     assert_err, assert_noop, assert_ok,
-    sp_io::crypto,
-    sp_runtime::traits::{BlakeTwo256, Hash},
+    pallet_prelude::{
+        InvalidTransaction, TransactionLongevity, TransactionSource, ValidateUnsigned,
+    },
+    sp_io::{
+        crypto,
+        hashing::{blake2_256, sha2_256, twox_128},
+    },
+    sp_runtime::{
+        traits::{BlakeTwo256, Hash},
+        DispatchError,
+    },
+    storage::{unhashed, StoragePrefixedMap},
+    traits::OnFinalize,
+};
+use sp_core::{
+    sp_std::{collections::btree_set::BTreeSet, vec},
+    sr25519::Public,
+    testing::{ECDSA, SR25519},
+    H256, H512,
 };
-use sp_core::{sp_std::vec, sr25519::Public, testing::SR25519, H256, H512};
 
 fn tx_input_gen_no_signature() -> TransactionInput {
     TransactionInput {
-        outpoint: H256::from(GENESIS_UTXO),
-        sig_script: H512::zero(),
+        outpoint: genesis_outpoint(),
+        witness: vec![],
+        sighash: SigHash::All,
     }
 }
 
+// The message signed by the input at `index`, according to its sighash.
+fn sig_message(tx: &Transaction, index: usize) -> Vec<u8> {
+    signature_message(tx, index, tx.inputs[index].sighash).unwrap()
+}
+
 fn execute_with_alice<F>(mut execute: F)
 where
     F: FnMut(Public),
@@ -52,17 +90,100 @@ fn test_simple_tx() {
             outputs: vec![TransactionOutput::new(50, H256::from(alice_pub_key))],
         };
 
-        let alice_sig = crypto::sr25519_sign(SR25519, &alice_pub_key, &tx.encode()).unwrap();
-        tx.inputs[0].sig_script = H512::from(alice_sig);
-        let new_utxo_hash = BlakeTwo256::hash_of(&(&tx.encode(), 0 as u64));
+        let alice_sig =
+            crypto::sr25519_sign(SR25519, &alice_pub_key, &sig_message(&tx, 0)).unwrap();
+        tx.inputs[0].witness = vec![alice_sig.0.to_vec()];
+        let new_utxo_hash = tx.outpoint(0);
 
-        assert_ok!(Utxo::spend(Origin::signed(0), tx));
-        assert!(!UtxoStore::<Test>::contains_key(H256::from(GENESIS_UTXO)));
+        assert_ok!(Utxo::spend(Origin::none(), tx));
+        assert!(!UtxoStore::<Test>::contains_key(genesis_outpoint()));
         assert!(UtxoStore::<Test>::contains_key(new_utxo_hash));
         assert_eq!(50, UtxoStore::<Test>::get(new_utxo_hash).unwrap().value);
     })
 }
 
+#[test]
+fn test_validate_unsigned() {
+    execute_with_alice(|alice_pub_key| {
+        let mut tx = Transaction {
+            inputs: vec![tx_input_gen_no_signature()],
+            outputs: vec![TransactionOutput::new(50, H256::from(alice_pub_key))],
+        };
+
+        let alice_sig =
+            crypto::sr25519_sign(SR25519, &alice_pub_key, &sig_message(&tx, 0)).unwrap();
+        tx.inputs[0].witness = vec![alice_sig.0.to_vec()];
+
+        let valid_tx =
+            Utxo::validate_unsigned(TransactionSource::External, &UtxoCall::spend(tx.clone()))
+                .unwrap();
+        assert_eq!(valid_tx.priority, 50);
+        assert!(valid_tx.requires.is_empty());
+        assert_eq!(
+            valid_tx.provides,
+            vec![
+                (b"spent", genesis_outpoint()).encode(),
+                tx.outpoint(0).encode()
+            ]
+        );
+        assert_ok!(Utxo::pre_dispatch(&UtxoCall::spend(tx.clone())));
+
+        // the pool gets the error code instead of a generic failure
+        tx.inputs[0].witness = vec![H512::random().as_bytes().to_vec()];
+        assert_eq!(
+            Utxo::validate_unsigned(TransactionSource::External, &UtxoCall::spend(tx)),
+            Err(InvalidTransaction::from(Error::<Test>::SignatureFailure).into())
+        );
+    })
+}
+
+#[test]
+fn test_conflicting_spends() {
+    execute_with_alice(|alice_pub_key| {
+        let mut txs = Vec::new();
+        for value in &[50, 60] {
+            let mut tx = Transaction {
+                inputs: vec![tx_input_gen_no_signature()],
+                outputs: vec![TransactionOutput::new(*value, H256::from(alice_pub_key))],
+            };
+            let alice_sig =
+                crypto::sr25519_sign(SR25519, &alice_pub_key, &sig_message(&tx, 0)).unwrap();
+            tx.inputs[0].witness = vec![alice_sig.0.to_vec()];
+            txs.push(tx);
+        }
+
+        // both spend the genesis utxo, so the pool keeps only one of them
+        let validity: Vec<_> = txs
+            .iter()
+            .map(|tx| crate::validate_transaction::<Test>(tx).unwrap())
+            .collect();
+        assert!(validity[1].provides.contains(&validity[0].provides[0]));
+
+        // once the first is in the block, the second may not follow it
+        assert_ok!(Utxo::spend(Origin::none(), txs[0].clone()));
+        assert_eq!(
+            Utxo::pre_dispatch(&UtxoCall::spend(txs[1].clone())),
+            Err(InvalidTransaction::from(Error::<Test>::MissingInputs).into())
+        );
+    })
+}
+
+#[test]
+fn attack_with_signed_origin() {
+    execute_with_alice(|alice_pub_key| {
+        let mut tx = Transaction {
+            inputs: vec![tx_input_gen_no_signature()],
+            outputs: vec![TransactionOutput::new(50, H256::from(alice_pub_key))],
+        };
+
+        let alice_sig =
+            crypto::sr25519_sign(SR25519, &alice_pub_key, &sig_message(&tx, 0)).unwrap();
+        tx.inputs[0].witness = vec![alice_sig.0.to_vec()];
+
+        assert_noop!(Utxo::spend(Origin::signed(0), tx), DispatchError::BadOrigin);
+    })
+}
+
 #[test]
 fn attack_with_sending_to_own_account() {
     let (mut test_ext, _alice, karl_pub_key) = new_test_ext_and_keys();
@@ -70,16 +191,20 @@ fn attack_with_sending_to_own_account() {
         // Karl wants to send himself a new utxo of value 50 out of thin air.
         let mut tx = Transaction {
             inputs: vec![TransactionInput {
-                outpoint: H256::zero(),
-                sig_script: H512::zero(),
+                outpoint: OutPoint::new(H256::zero(), 0),
+                witness: vec![],
+                sighash: SigHash::All,
             }],
             outputs: vec![TransactionOutput::new(50, H256::from(karl_pub_key))],
         };
 
-        let karl_sig = crypto::sr25519_sign(SR25519, &karl_pub_key, &tx.encode()).unwrap();
-        tx.inputs[0].sig_script = H512::from(karl_sig);
+        let karl_sig = crypto::sr25519_sign(SR25519, &karl_pub_key, &sig_message(&tx, 0)).unwrap();
+        tx.inputs[0].witness = vec![karl_sig.0.to_vec()];
 
-        assert_noop!(Utxo::spend(Origin::signed(0), tx), "missing inputs");
+        assert_noop!(
+            Utxo::spend(Origin::none(), tx),
+            Error::<Test>::MissingInputs
+        );
     });
 }
 
@@ -87,19 +212,19 @@ fn attack_with_sending_to_own_account() {
 fn attack_with_empty_transactions() {
     new_test_ext().execute_with(|| {
         assert_err!(
-            Utxo::spend(Origin::signed(0), Transaction::default()), // empty tx
-            "no inputs"
+            Utxo::spend(Origin::none(), Transaction::default()), // empty tx
+            Error::<Test>::NoInputs
         );
 
         assert_err!(
             Utxo::spend(
-                Origin::signed(0),
+                Origin::none(),
                 Transaction {
                     inputs: vec![TransactionInput::default()], // an empty tx
                     outputs: vec![]
                 }
             ),
-            "no outputs"
+            Error::<Test>::NoOutputs
         );
     });
 }
@@ -116,14 +241,41 @@ fn attack_by_double_counting_input() {
             outputs: vec![TransactionOutput::new(100, H256::from(alice_pub_key))],
         };
 
-        let alice_sig = crypto::sr25519_sign(SR25519, &alice_pub_key, &tx.encode()).unwrap();
+        let alice_sig =
+            crypto::sr25519_sign(SR25519, &alice_pub_key, &sig_message(&tx, 0)).unwrap();
+
+        tx.inputs[0].witness = vec![alice_sig.0.to_vec()];
+        tx.inputs[1].witness = vec![alice_sig.0.to_vec()];
+
+        assert_err!(
+            Utxo::spend(Origin::none(), tx),
+            Error::<Test>::DuplicateInput
+        );
+    });
+}
+
+#[test]
+fn attack_by_double_counting_input_with_other_sighash() {
+    execute_with_alice(|alice_pub_key| {
+        // the same UTXO twice, under different sighashes and so with different signatures
+        let mut tx = Transaction {
+            inputs: vec![
+                tx_input_gen_no_signature(),
+                TransactionInput::new_with_sighash(genesis_outpoint(), vec![], SigHash::None),
+            ],
+            outputs: vec![TransactionOutput::new(200, H256::from(alice_pub_key))],
+        };
 
-        tx.inputs[0].sig_script = H512::from(alice_sig.clone());
-        tx.inputs[1].sig_script = H512::from(alice_sig);
+        for index in 0..2 {
+            let alice_sig =
+                crypto::sr25519_sign(SR25519, &alice_pub_key, &sig_message(&tx, index)).unwrap();
+            tx.inputs[index].witness = vec![alice_sig.0.to_vec()];
+        }
+        assert_ne!(tx.inputs[0], tx.inputs[1]);
 
         assert_err!(
-            Utxo::spend(Origin::signed(0), tx),
-            "each input should be used only once"
+            Utxo::spend(Origin::none(), tx),
+            Error::<Test>::DuplicateInput
         );
     });
 }
@@ -133,16 +285,62 @@ fn attack_with_invalid_signature() {
     execute_with_alice(|alice_pub_key| {
         let tx = Transaction {
             inputs: vec![TransactionInput {
-                outpoint: H256::from(GENESIS_UTXO),
+                outpoint: genesis_outpoint(),
                 // Just a random signature!
-                sig_script: H512::random(),
+                witness: vec![H512::random().as_bytes().to_vec()],
+                sighash: SigHash::All,
             }],
             outputs: vec![TransactionOutput::new(100, H256::from(alice_pub_key))],
         };
 
         assert_err!(
-            Utxo::spend(Origin::signed(0), tx),
-            "signature must be valid"
+            Utxo::spend(Origin::none(), tx),
+            Error::<Test>::SignatureFailure
+        );
+    });
+}
+
+#[test]
+fn attack_by_duplicating_outputs() {
+    execute_with_alice(|alice_pub_key| {
+        let mut tx = Transaction {
+            inputs: vec![tx_input_gen_no_signature()],
+            // the same output twice would collide in the utxo store
+            outputs: vec![
+                TransactionOutput::new(50, H256::from(alice_pub_key)),
+                TransactionOutput::new(50, H256::from(alice_pub_key)),
+            ],
+        };
+
+        let alice_sig =
+            crypto::sr25519_sign(SR25519, &alice_pub_key, &sig_message(&tx, 0)).unwrap();
+        tx.inputs[0].witness = vec![alice_sig.0.to_vec()];
+
+        assert_noop!(
+            Utxo::spend(Origin::none(), tx),
+            Error::<Test>::DuplicateOutput
+        );
+    });
+}
+
+#[test]
+fn attack_with_invalid_header() {
+    execute_with_alice(|alice_pub_key| {
+        let mut output = TransactionOutput::new(50, H256::from(alice_pub_key));
+        // signature method 0b111 is not supported
+        output.header = 0b111;
+        let mut tx = Transaction {
+            inputs: vec![tx_input_gen_no_signature()],
+            outputs: vec![output],
+        };
+
+        let alice_sig =
+            crypto::sr25519_sign(SR25519, &alice_pub_key, &sig_message(&tx, 0)).unwrap();
+        tx.inputs[0].witness = vec![alice_sig.0.to_vec()];
+
+        assert_noop!(
+            Utxo::spend(Origin::none(), tx),
+            Error::<Test>::InvalidHeader
         );
     });
 }
@@ -156,12 +354,13 @@ fn attack_by_permanently_sinking_outputs() {
             outputs: vec![TransactionOutput::new(0, H256::from(alice_pub_key))],
         };
 
-        let alice_sig = crypto::sr25519_sign(SR25519, &alice_pub_key, &tx.encode()).unwrap();
-        tx.inputs[0].sig_script = H512::from(alice_sig);
+        let alice_sig =
+            crypto::sr25519_sign(SR25519, &alice_pub_key, &sig_message(&tx, 0)).unwrap();
+        tx.inputs[0].witness = vec![alice_sig.0.to_vec()];
 
         assert_noop!(
-            Utxo::spend(Origin::signed(0), tx),
-            "output value must be nonzero"
+            Utxo::spend(Origin::none(), tx),
+            Error::<Test>::ZeroOutputValue
         );
     });
 }
@@ -178,10 +377,14 @@ fn attack_by_overflowing_value() {
             ],
         };
 
-        let alice_sig = crypto::sr25519_sign(SR25519, &alice_pub_key, &tx.encode()).unwrap();
-        tx.inputs[0].sig_script = H512::from(alice_sig);
+        let alice_sig =
+            crypto::sr25519_sign(SR25519, &alice_pub_key, &sig_message(&tx, 0)).unwrap();
+        tx.inputs[0].witness = vec![alice_sig.0.to_vec()];
 
-        assert_err!(Utxo::spend(Origin::signed(0), tx), "output value overflow");
+        assert_err!(
+            Utxo::spend(Origin::none(), tx),
+            Error::<Test>::OutputValueOverflow
+        );
     });
 }
 
@@ -197,12 +400,13 @@ fn attack_by_overspending() {
             ],
         };
 
-        let alice_sig = crypto::sr25519_sign(SR25519, &alice_pub_key, &tx.encode()).unwrap();
-        tx.inputs[0].sig_script = H512::from(alice_sig);
+        let alice_sig =
+            crypto::sr25519_sign(SR25519, &alice_pub_key, &sig_message(&tx, 0)).unwrap();
+        tx.inputs[0].witness = vec![alice_sig.0.to_vec()];
 
         assert_noop!(
-            Utxo::spend(Origin::signed(0), tx),
-            "output value must not exceed input value"
+            Utxo::spend(Origin::none(), tx),
+            Error::<Test>::OutputExceedsInput
         );
     })
 }
@@ -222,22 +426,24 @@ fn tx_from_alice_to_karl() {
             ],
         };
 
-        let alice_sig = crypto::sr25519_sign(SR25519, &alice_pub_key, &tx.encode()).unwrap();
-        tx.inputs[0].sig_script = H512::from(alice_sig);
+        let alice_sig =
+            crypto::sr25519_sign(SR25519, &alice_pub_key, &sig_message(&tx, 0)).unwrap();
+        tx.inputs[0].witness = vec![alice_sig.0.to_vec()];
 
-        assert_ok!(Utxo::spend(Origin::signed(0), tx.clone()));
-        let new_utxo_hash = BlakeTwo256::hash_of(&(&tx.encode(), 1 as u64));
+        assert_ok!(Utxo::spend(Origin::none(), tx.clone()));
+        let new_utxo_hash = tx.outpoint(1);
 
         // then send rest of the tokens to karl (proving that the first tx was successful)
         let mut tx = Transaction {
-            inputs: vec![TransactionInput::new(new_utxo_hash, H512::zero())],
+            inputs: vec![TransactionInput::new(new_utxo_hash, vec![])],
             outputs: vec![TransactionOutput::new(90, H256::from(karl_pub_key))],
         };
 
-        let alice_sig = crypto::sr25519_sign(SR25519, &alice_pub_key, &tx.encode()).unwrap();
-        tx.inputs[0].sig_script = H512::from(alice_sig);
+        let alice_sig =
+            crypto::sr25519_sign(SR25519, &alice_pub_key, &sig_message(&tx, 0)).unwrap();
+        tx.inputs[0].witness = vec![alice_sig.0.to_vec()];
 
-        assert_ok!(Utxo::spend(Origin::signed(0), tx));
+        assert_ok!(Utxo::spend(Origin::none(), tx));
     });
 }
 
@@ -250,16 +456,2839 @@ fn test_reward() {
             outputs: vec![TransactionOutput::new(90, H256::from(alice_pub_key))],
         };
 
-        let alice_sig = crypto::sr25519_sign(SR25519, &alice_pub_key, &tx.encode()).unwrap();
-        tx.inputs[0].sig_script = H512::from(alice_sig);
-        assert_ok!(Utxo::spend(Origin::signed(0), tx.clone()));
+        let alice_sig =
+            crypto::sr25519_sign(SR25519, &alice_pub_key, &sig_message(&tx, 0)).unwrap();
+        tx.inputs[0].witness = vec![alice_sig.0.to_vec()];
+        assert_ok!(Utxo::spend(Origin::none(), tx.clone()));
 
         // if the previous spend succeeded, there should be one utxo
         // that has a value of 90 and a reward that has a value of 10
         let utxos = UtxoStore::<Test>::iter_values().next().unwrap().unwrap();
-        let reward = RewardTotal::<Test>::get();
+        let reward = RewardTotal::<Test>::get(TokenType::MLT as TokenID);
 
         assert_eq!(utxos.value, 90);
         assert_eq!(reward, 10);
     })
 }
+
+#[test]
+fn error_codes_are_distinct() {
+    let errors = vec![
+        Error::<Test>::NoInputs,
+        Error::<Test>::NoOutputs,
+        Error::<Test>::DuplicateInput,
+        Error::<Test>::DuplicateOutput,
+        Error::<Test>::SignatureFailure,
+        Error::<Test>::ZeroOutputValue,
+        Error::<Test>::OutputIndexOverflow,
+        Error::<Test>::OutputAlreadyExists,
+        Error::<Test>::InvalidHeader,
+        Error::<Test>::InputValueOverflow,
+        Error::<Test>::OutputValueOverflow,
+        Error::<Test>::OutputExceedsInput,
+        Error::<Test>::TokenInputNotFound,
+        Error::<Test>::RewardUnderflow,
+        Error::<Test>::RewardOverflow,
+        Error::<Test>::MissingInputs,
+        Error::<Test>::SigHashSingleWithoutOutput,
+        Error::<Test>::UnsupportedSignatureMethod,
+        Error::<Test>::WitnessTooLarge,
+        Error::<Test>::InvalidWitness,
+        Error::<Test>::ThresholdNotMet,
+        Error::<Test>::DuplicateSigner,
+        Error::<Test>::InvalidDestination,
+        Error::<Test>::TimelockNotExpired,
+        Error::<Test>::InvalidPreimage,
+        Error::<Test>::ScriptFailed,
+        Error::<Test>::InvalidPolicy,
+        Error::<Test>::InvalidTicker,
+        Error::<Test>::TooManyDecimals,
+        Error::<Test>::ZeroSupply,
+        Error::<Test>::TokenIdOverflow,
+        Error::<Test>::TokenNotFound,
+        Error::<Test>::SupplyCapExceeded,
+        Error::<Test>::InvalidNft,
+        Error::<Test>::NftNotTransferred,
+        Error::<Test>::NftAlreadyExists,
+        Error::<Test>::WrongTokenKind,
+        Error::<Test>::InsufficientFee,
+        Error::<Test>::TooManyZkInputs,
+    ];
+    let count = errors.len();
+
+    let codes: BTreeSet<_> = errors
+        .into_iter()
+        .map(|err| match InvalidTransaction::from(err) {
+            InvalidTransaction::Custom(code) => code,
+            other => panic!("unexpected validity error {:?}", other),
+        })
+        .collect();
+    assert_eq!(codes.len(), count);
+}
+
+#[test]
+fn test_reward_outpoints() {
+    execute_with_alice(|alice_pub_key| {
+        RewardTotal::<Test>::insert(TokenType::MLT as TokenID, 10);
+        Utxo::on_finalize(7);
+
+        // the reward is the first output of the coinbase transaction of block 7
+        let coinbase = Transaction {
+            inputs: vec![TransactionInput::new(
+                OutPoint::new(H256::zero(), 7),
+                vec![],
+            )],
+            outputs: vec![TransactionOutput::new(10, H256::from(alice_pub_key))],
+        };
+        let reward = UtxoStore::<Test>::get(coinbase.outpoint(0)).unwrap();
+        assert_eq!(reward.value, 10);
+        assert_eq!(RewardTotal::<Test>::get(TokenType::MLT as TokenID), 0);
+    })
+}
+
+// Makes alice and karl the block authors, in that order.
+fn set_authorities(alice_pub_key: Public, karl_pub_key: Public) {
+    let authorities = vec![H256::from(alice_pub_key), H256::from(karl_pub_key)];
+    unhashed::put(
+        &[twox_128(b"Aura"), twox_128(b"Authorities")].concat(),
+        &authorities,
+    );
+}
+
+#[test]
+fn test_reward_dust() {
+    execute_with_token(|alice_pub_key, token_id, _| {
+        let karl_pub_key = crypto::sr25519_generate(SR25519, None);
+        set_authorities(alice_pub_key, karl_pub_key);
+        RewardTotal::<Test>::insert(TokenType::MLT as TokenID, 11);
+        RewardTotal::<Test>::insert(token_id, 1);
+
+        // what does not split evenly goes to karl, whose turn it is in block 7, and nothing is
+        // left behind
+        Utxo::on_finalize(7);
+        let coinbase = Transaction {
+            inputs: vec![TransactionInput::new(
+                OutPoint::new(H256::zero(), 7),
+                vec![],
+            )],
+            outputs: vec![
+                TransactionOutput::new(5, H256::from(alice_pub_key)),
+                TransactionOutput::new(6, H256::from(karl_pub_key)),
+                TransactionOutput::new(1, H256::from(karl_pub_key)).with_token_id(token_id),
+            ],
+        };
+        for (index, output) in coinbase.outputs.iter().enumerate() {
+            assert_eq!(
+                UtxoStore::<Test>::get(coinbase.outpoint(index as u64)).as_ref(),
+                Some(output)
+            );
+        }
+        assert_eq!(RewardTotal::<Test>::iter().count(), 0);
+    })
+}
+
+#[test]
+fn attack_with_many_reward_tokens() {
+    execute_with_alice(|_| {
+        // each issued token holds a reward, however small
+        for token_id in 0..=MAX_REWARD_TOKENS as TokenID {
+            RewardTotal::<Test>::insert(FIRST_ISSUED_TOKEN_ID + token_id, 1);
+        }
+
+        // no more are paid out in a block than `on_initialize` accounts for
+        Utxo::on_finalize(7);
+        assert_eq!(RewardTotal::<Test>::iter().count(), 1);
+        Utxo::on_finalize(8);
+        assert_eq!(RewardTotal::<Test>::iter().count(), 0);
+    })
+}
+
+#[test]
+fn test_reward_tokens_take_turns() {
+    execute_with_alice(|_| {
+        let mlt = TokenType::MLT as TokenID;
+        let tokens: Vec<TokenID> = (0..2 * MAX_REWARD_TOKENS as TokenID)
+            .map(|token_id| FIRST_ISSUED_TOKEN_ID + token_id)
+            .collect();
+
+        // more tokens than fit in a block keep collecting fees, yet MLT is paid out in every
+        // block and each other token within three blocks
+        let mut paid = BTreeSet::new();
+        for block in 7..10 {
+            RewardTotal::<Test>::insert(mlt, 10);
+            for token_id in &tokens {
+                RewardTotal::<Test>::insert(token_id, 1);
+            }
+            Utxo::on_finalize(block);
+            assert!(!RewardTotal::<Test>::contains_key(mlt));
+            paid.extend(
+                tokens
+                    .iter()
+                    .copied()
+                    .filter(|token_id| !RewardTotal::<Test>::contains_key(token_id)),
+            );
+        }
+        assert_eq!(paid.len(), tokens.len());
+    })
+}
+
+#[test]
+fn test_reward_without_authorities() {
+    new_test_ext().execute_with(|| {
+        // no block authors at all
+        unhashed::kill(&[twox_128(b"Aura"), twox_128(b"Authorities")].concat());
+        assert!(Aura::authorities().is_empty());
+        RewardTotal::<Test>::insert(TokenType::MLT as TokenID, 10);
+
+        // the block is finalized, and the reward kept for later
+        Utxo::on_finalize(7);
+        assert_eq!(RewardTotal::<Test>::get(TokenType::MLT as TokenID), 10);
+    })
+}
+
+#[test]
+fn test_migrate_legacy_utxos() {
+    execute_with_alice(|alice_pub_key| {
+        let utxo = migrations::LegacyTransactionOutput {
+            value: 20,
+            pub_key: H256::from(alice_pub_key),
+            header: 0,
+        };
+        let old_hash = BlakeTwo256::hash_of(&(&Transaction::default().encode(), 0 as u64));
+
+        // the layout of an entry before outpoints were introduced
+        let mut raw_key = UtxoStore::<Test>::final_prefix().to_vec();
+        raw_key.extend_from_slice(&blake2_256(old_hash.as_bytes()));
+        unhashed::put(&raw_key, &Some(utxo.clone()));
+
+        // events are not kept for the genesis block
+        System::set_block_number(1);
+        migrations::migrate_to_outpoints::<Test>();
+
+        assert!(unhashed::get_raw(&raw_key).is_none());
+        let new_outpoint = migrations::legacy_outpoint(old_hash);
+        let new_key = UtxoStore::<Test>::hashed_key_for(&new_outpoint);
+        assert_eq!(unhashed::get(&new_key), Some(Some(utxo.clone())));
+        // the holder of the key is told where the utxo went
+        let migrated =
+            Event::pallet_utxo(crate::Event::LegacyUtxoMigrated(utxo.pub_key, new_outpoint));
+        assert_eq!(
+            System::events()
+                .into_iter()
+                .filter(|record| record.event == migrated)
+                .count(),
+            1
+        );
+        // entries keyed by outpoints are not touched
+        assert_eq!(
+            UtxoStore::<Test>::get(genesis_outpoint()).unwrap().value,
+            100
+        );
+
+        // a second run has nothing left to do
+        let raw_before = unhashed::get_raw(&new_key);
+        migrations::migrate_to_outpoints::<Test>();
+        assert_eq!(unhashed::get_raw(&new_key), raw_before);
+        assert_eq!(
+            System::events()
+                .into_iter()
+                .filter(|record| record.event == migrated)
+                .count(),
+            1
+        );
+        assert_eq!(
+            UtxoStore::<Test>::get(genesis_outpoint()).unwrap().value,
+            100
+        );
+    })
+}
+
+#[test]
+fn test_txid_ignores_signatures() {
+    execute_with_alice(|alice_pub_key| {
+        let mut tx = Transaction {
+            inputs: vec![tx_input_gen_no_signature()],
+            outputs: vec![TransactionOutput::new(50, H256::from(alice_pub_key))],
+        };
+        let mut malleated_tx = tx.clone();
+
+        // sr25519 signatures are randomised, so signing twice gives two valid signatures
+        let other_sig =
+            crypto::sr25519_sign(SR25519, &alice_pub_key, &sig_message(&malleated_tx, 0)).unwrap();
+        malleated_tx.inputs[0].witness = vec![other_sig.0.to_vec()];
+        let alice_sig =
+            crypto::sr25519_sign(SR25519, &alice_pub_key, &sig_message(&tx, 0)).unwrap();
+        tx.inputs[0].witness = vec![alice_sig.0.to_vec()];
+
+        assert_ne!(tx.wtxid(), malleated_tx.wtxid());
+        assert_eq!(tx.txid(), malleated_tx.txid());
+
+        // a child of the original transaction stays valid when the malleated one gets in
+        let mut child_tx = Transaction {
+            inputs: vec![TransactionInput::new(tx.outpoint(0), vec![])],
+            outputs: vec![TransactionOutput::new(40, H256::from(alice_pub_key))],
+        };
+        let alice_sig =
+            crypto::sr25519_sign(SR25519, &alice_pub_key, &sig_message(&child_tx, 0)).unwrap();
+        child_tx.inputs[0].witness = vec![alice_sig.0.to_vec()];
+
+        assert_ok!(Utxo::spend(Origin::none(), malleated_tx));
+        assert_ok!(Utxo::spend(Origin::none(), child_tx));
+    })
+}
+
+// alice signs her input before karl adds his own to pay the fee
+#[test]
+fn test_sighash_anyone_can_pay() {
+    let (mut test_ext, alice_pub_key, karl_pub_key) = new_test_ext_and_keys();
+    test_ext.execute_with(|| {
+        let mut tx = Transaction {
+            inputs: vec![tx_input_gen_no_signature()],
+            outputs: vec![
+                TransactionOutput::new(60, H256::from(alice_pub_key)),
+                TransactionOutput::new(40, H256::from(karl_pub_key)),
+            ],
+        };
+        let alice_sig =
+            crypto::sr25519_sign(SR25519, &alice_pub_key, &sig_message(&tx, 0)).unwrap();
+        tx.inputs[0].witness = vec![alice_sig.0.to_vec()];
+        assert_ok!(Utxo::spend(Origin::none(), tx.clone()));
+
+        let mut sponsored_tx = Transaction {
+            inputs: vec![TransactionInput::new_with_sighash(
+                tx.outpoint(0),
+                vec![],
+                SigHash::AllAnyoneCanPay,
+            )],
+            outputs: vec![TransactionOutput::new(60, H256::from(karl_pub_key))],
+        };
+        let alice_sig =
+            crypto::sr25519_sign(SR25519, &alice_pub_key, &sig_message(&sponsored_tx, 0)).unwrap();
+        sponsored_tx.inputs[0].witness = vec![alice_sig.0.to_vec()];
+
+        // karl adds his input afterwards, which becomes the reward
+        sponsored_tx
+            .inputs
+            .push(TransactionInput::new(tx.outpoint(1), vec![]));
+        let karl_sig =
+            crypto::sr25519_sign(SR25519, &karl_pub_key, &sig_message(&sponsored_tx, 1)).unwrap();
+        sponsored_tx.inputs[1].witness = vec![karl_sig.0.to_vec()];
+
+        assert_ok!(Utxo::spend(Origin::none(), sponsored_tx));
+        assert_eq!(RewardTotal::<Test>::get(TokenType::MLT as TokenID), 40);
+    })
+}
+
+#[test]
+fn test_sighash_single() {
+    execute_with_alice(|alice_pub_key| {
+        let mut tx = Transaction {
+            inputs: vec![TransactionInput::new_with_sighash(
+                genesis_outpoint(),
+                vec![],
+                SigHash::Single,
+            )],
+            outputs: vec![TransactionOutput::new(50, H256::from(alice_pub_key))],
+        };
+        let alice_sig =
+            crypto::sr25519_sign(SR25519, &alice_pub_key, &sig_message(&tx, 0)).unwrap();
+        tx.inputs[0].witness = vec![alice_sig.0.to_vec()];
+
+        // the other outputs are not signed and can still be added
+        tx.outputs
+            .push(TransactionOutput::new(30, H256::from(alice_pub_key)));
+        let valid_tx = crate::validate_transaction::<Test>(&tx).unwrap();
+        assert_eq!(valid_tx.priority, 20);
+
+        // but the signed one cannot be changed
+        let mut tampered_tx = tx.clone();
+        tampered_tx.outputs[0].value = 40;
+        assert_noop!(
+            Utxo::spend(Origin::none(), tampered_tx),
+            Error::<Test>::SignatureFailure
+        );
+
+        assert_ok!(Utxo::spend(Origin::none(), tx));
+    })
+}
+
+#[test]
+fn attack_by_moving_sighash_single_anyone_can_pay_input() {
+    let (mut test_ext, alice_pub_key, karl_pub_key) = new_test_ext_and_keys();
+    test_ext.execute_with(|| {
+        let mut tx = Transaction {
+            inputs: vec![tx_input_gen_no_signature()],
+            outputs: vec![
+                TransactionOutput::new(60, H256::from(alice_pub_key)),
+                TransactionOutput::new(40, H256::from(karl_pub_key)),
+            ],
+        };
+        let alice_sig =
+            crypto::sr25519_sign(SR25519, &alice_pub_key, &sig_message(&tx, 0)).unwrap();
+        tx.inputs[0].witness = vec![alice_sig.0.to_vec()];
+        assert_ok!(Utxo::spend(Origin::none(), tx.clone()));
+
+        // alice only signs her input and the output at its index
+        let alice_input =
+            TransactionInput::new_with_sighash(tx.outpoint(0), vec![], SigHash::SingleAnyoneCanPay);
+        let alice_output = TransactionOutput::new(50, H256::from(alice_pub_key));
+        let mut signed_tx = Transaction {
+            inputs: vec![alice_input],
+            outputs: vec![alice_output.clone()],
+        };
+        let alice_sig =
+            crypto::sr25519_sign(SR25519, &alice_pub_key, &sig_message(&signed_tx, 0)).unwrap();
+        signed_tx.inputs[0].witness = vec![alice_sig.0.to_vec()];
+
+        // karl puts his own input and output first, with alice's pair moved after them
+        let mut moved_tx = Transaction {
+            inputs: vec![
+                TransactionInput::new(tx.outpoint(1), vec![]),
+                signed_tx.inputs[0].clone(),
+            ],
+            outputs: vec![
+                TransactionOutput::new(30, H256::from(karl_pub_key)),
+                alice_output.clone(),
+            ],
+        };
+        let karl_sig =
+            crypto::sr25519_sign(SR25519, &karl_pub_key, &sig_message(&moved_tx, 0)).unwrap();
+        moved_tx.inputs[0].witness = vec![karl_sig.0.to_vec()];
+        assert_noop!(
+            Utxo::spend(Origin::none(), moved_tx),
+            Error::<Test>::SignatureFailure
+        );
+
+        // added after alice's pair, karl's input and output are accepted
+        signed_tx
+            .inputs
+            .push(TransactionInput::new(tx.outpoint(1), vec![]));
+        signed_tx
+            .outputs
+            .push(TransactionOutput::new(30, H256::from(karl_pub_key)));
+        let karl_sig =
+            crypto::sr25519_sign(SR25519, &karl_pub_key, &sig_message(&signed_tx, 1)).unwrap();
+        signed_tx.inputs[1].witness = vec![karl_sig.0.to_vec()];
+        assert_ok!(Utxo::spend(Origin::none(), signed_tx));
+    })
+}
+
+#[test]
+fn attack_by_changing_sighash() {
+    execute_with_alice(|alice_pub_key| {
+        let mut tx = Transaction {
+            inputs: vec![TransactionInput::new_with_sighash(
+                genesis_outpoint(),
+                vec![],
+                SigHash::None,
+            )],
+            outputs: vec![TransactionOutput::new(50, H256::from(alice_pub_key))],
+        };
+        let alice_sig =
+            crypto::sr25519_sign(SR25519, &alice_pub_key, &sig_message(&tx, 0)).unwrap();
+        tx.inputs[0].witness = vec![alice_sig.0.to_vec()];
+
+        // the signature commits to the sighash, so it cannot be reused for another mode
+        tx.inputs[0].sighash = SigHash::NoneAnyoneCanPay;
+        assert_noop!(
+            Utxo::spend(Origin::none(), tx),
+            Error::<Test>::SignatureFailure
+        );
+    })
+}
+
+#[test]
+fn attack_with_sighash_single_without_output() {
+    let (mut test_ext, alice_pub_key, karl_pub_key) = new_test_ext_and_keys();
+    test_ext.execute_with(|| {
+        let mut tx = Transaction {
+            inputs: vec![tx_input_gen_no_signature()],
+            outputs: vec![
+                TransactionOutput::new(60, H256::from(alice_pub_key)),
+                TransactionOutput::new(40, H256::from(karl_pub_key)),
+            ],
+        };
+        let alice_sig =
+            crypto::sr25519_sign(SR25519, &alice_pub_key, &sig_message(&tx, 0)).unwrap();
+        tx.inputs[0].witness = vec![alice_sig.0.to_vec()];
+        assert_ok!(Utxo::spend(Origin::none(), tx.clone()));
+
+        // karl's input is the second one, but there is only one output
+        let mut tx = Transaction {
+            inputs: vec![
+                TransactionInput::new(tx.outpoint(0), vec![]),
+                TransactionInput::new_with_sighash(tx.outpoint(1), vec![], SigHash::Single),
+            ],
+            outputs: vec![TransactionOutput::new(100, H256::from(karl_pub_key))],
+        };
+        let alice_sig =
+            crypto::sr25519_sign(SR25519, &alice_pub_key, &sig_message(&tx, 0)).unwrap();
+        tx.inputs[0].witness = vec![alice_sig.0.to_vec()];
+
+        assert_noop!(
+            Utxo::spend(Origin::none(), tx),
+            Error::<Test>::SigHashSingleWithoutOutput
+        );
+    })
+}
+
+#[test]
+fn attack_with_signature_of_another_method() {
+    execute_with_alice(|alice_pub_key| {
+        let methods = vec![
+            SignatureMethod::BLS,
+            SignatureMethod::ZkSnark,
+            SignatureMethod::Ecdsa,
+        ];
+        for sign_method in methods {
+            // a utxo of alice which claims another signature method than sr25519
+            let mut utxo = TransactionOutput::new(100, H256::from(alice_pub_key));
+            let mut header = OutputHeader::new(utxo.header);
+            header.set_sign_method(sign_method);
+            utxo.header = header.as_u128();
+            let outpoint = OutPoint::new(H256::random(), 0);
+            UtxoStore::<Test>::insert(outpoint, Some(utxo));
+
+            let mut tx = Transaction {
+                inputs: vec![TransactionInput::new(outpoint, vec![])],
+                outputs: vec![TransactionOutput::new(50, H256::from(alice_pub_key))],
+            };
+            let alice_sig =
+                crypto::sr25519_sign(SR25519, &alice_pub_key, &sig_message(&tx, 0)).unwrap();
+            tx.inputs[0].witness = vec![alice_sig.0.to_vec()];
+
+            assert_noop!(
+                Utxo::spend(Origin::none(), tx),
+                Error::<Test>::SignatureFailure
+            );
+        }
+    })
+}
+
+#[test]
+fn test_migrate_schnorr_headers() {
+    execute_with_alice(|alice_pub_key| {
+        assert_eq!(StorageVersion::<Test>::get(), Releases::V8);
+        assert_eq!(
+            OutputHeader::new(UtxoStore::<Test>::get(genesis_outpoint()).unwrap().header)
+                .sign_method(),
+            Some(SignatureMethod::Schnorr)
+        );
+
+        // a chain at V2 only holds utxos of the legacy layout
+        UtxoStore::<Test>::remove(genesis_outpoint());
+        // a utxo created before `TransactionOutput::new` picked the Schnorr method
+        let utxo = migrations::LegacyTransactionOutput {
+            value: 20,
+            pub_key: H256::from(alice_pub_key),
+            header: 0,
+        };
+        let outpoint = OutPoint::new(H256::random(), 0);
+        unhashed::put(&UtxoStore::<Test>::hashed_key_for(outpoint), &Some(utxo));
+        StorageVersion::<Test>::put(Releases::V2);
+
+        migrations::migrate::<Test>();
+
+        assert_eq!(StorageVersion::<Test>::get(), Releases::V8);
+        let utxo = UtxoStore::<Test>::get(outpoint).unwrap();
+        assert_eq!(
+            OutputHeader::new(utxo.header).sign_method(),
+            Some(SignatureMethod::Schnorr)
+        );
+        assert_eq!(
+            utxo.destination,
+            Destination::Pubkey(H256::from(alice_pub_key))
+        );
+    })
+}
+
+#[test]
+fn attack_with_revoked_key() {
+    execute_with_alice(|alice_pub_key| {
+        let mut tx = Transaction {
+            inputs: vec![tx_input_gen_no_signature()],
+            outputs: vec![TransactionOutput::new(50, H256::from(alice_pub_key))],
+        };
+        let alice_sig =
+            crypto::sr25519_sign(SR25519, &alice_pub_key, &sig_message(&tx, 0)).unwrap();
+        tx.inputs[0].witness = vec![alice_sig.0.to_vec()];
+
+        // the mock runtime's verifier refuses alice's otherwise valid signature
+        REVOKED_KEYS.with(|keys| keys.borrow_mut().push(H256::from(alice_pub_key)));
+        assert_noop!(
+            Utxo::spend(Origin::none(), tx.clone()),
+            Error::<Test>::SignatureFailure
+        );
+
+        REVOKED_KEYS.with(|keys| keys.borrow_mut().clear());
+        assert_ok!(Utxo::spend(Origin::none(), tx));
+    })
+}
+
+#[test]
+fn test_ecdsa_signature() {
+    execute_with_alice(|alice_pub_key| {
+        let ecdsa_pub_key = crypto::ecdsa_generate(ECDSA, None);
+        let utxo = TransactionOutput::new_ecdsa(100, &ecdsa_pub_key.0);
+        let outpoint = OutPoint::new(H256::random(), 0);
+        UtxoStore::<Test>::insert(outpoint, Some(utxo.clone()));
+
+        let mut tx = Transaction {
+            inputs: vec![TransactionInput::new(outpoint, vec![])],
+            outputs: vec![TransactionOutput::new(100, H256::from(alice_pub_key))],
+        };
+        let message = sig_message(&tx, 0);
+        let ecdsa_sig = crypto::ecdsa_sign(ECDSA, &ecdsa_pub_key, &message).unwrap();
+
+        // a signature with its recovery id is accepted as well
+        assert_eq!(
+            <DefaultVerifier>::verify(
+                &utxo,
+                &ecdsa_key_hash(&ecdsa_pub_key.0),
+                &ecdsa_sig.0,
+                &message
+            ),
+            Ok(())
+        );
+        let mut recoverable_tx = tx.clone();
+        recoverable_tx.inputs[0].witness = vec![ecdsa_sig.0.to_vec()];
+        assert_ok!(crate::validate_transaction::<Test>(&recoverable_tx));
+
+        // the witness only holds r and s, the recovery id is found again by the verifier
+        tx.inputs[0].witness = vec![ecdsa_sig.0[..64].to_vec()];
+        assert_ok!(Utxo::spend(Origin::none(), tx));
+    })
+}
+
+#[test]
+fn attack_with_wrong_ecdsa_key() {
+    execute_with_alice(|alice_pub_key| {
+        let ecdsa_pub_key = crypto::ecdsa_generate(ECDSA, None);
+        let karl_ecdsa_pub_key = crypto::ecdsa_generate(ECDSA, None);
+        let utxo = TransactionOutput::new_ecdsa(100, &ecdsa_pub_key.0);
+        let outpoint = OutPoint::new(H256::random(), 0);
+        UtxoStore::<Test>::insert(outpoint, Some(utxo.clone()));
+
+        let mut tx = Transaction {
+            inputs: vec![TransactionInput::new(outpoint, vec![])],
+            outputs: vec![TransactionOutput::new(100, H256::from(alice_pub_key))],
+        };
+        let message = sig_message(&tx, 0);
+        let karl_sig = crypto::ecdsa_sign(ECDSA, &karl_ecdsa_pub_key, &message).unwrap();
+        assert_eq!(
+            <DefaultVerifier>::verify(
+                &utxo,
+                &ecdsa_key_hash(&ecdsa_pub_key.0),
+                &karl_sig.0,
+                &message
+            ),
+            Err(SignatureError::Invalid)
+        );
+
+        tx.inputs[0].witness = vec![karl_sig.0[..64].to_vec()];
+        assert_noop!(
+            Utxo::spend(Origin::none(), tx),
+            Error::<Test>::SignatureFailure
+        );
+    })
+}
+
+#[test]
+fn attack_with_invalid_witness() {
+    execute_with_alice(|alice_pub_key| {
+        let mut tx = Transaction {
+            inputs: vec![tx_input_gen_no_signature()],
+            outputs: vec![TransactionOutput::new(50, H256::from(alice_pub_key))],
+        };
+        assert_noop!(
+            Utxo::spend(Origin::none(), tx.clone()),
+            Error::<Test>::InvalidWitness
+        );
+
+        // a key output takes exactly one signature, extra items are refused
+        let alice_sig =
+            crypto::sr25519_sign(SR25519, &alice_pub_key, &sig_message(&tx, 0)).unwrap();
+        tx.inputs[0].witness = vec![alice_sig.0.to_vec(), vec![0]];
+        assert_noop!(
+            Utxo::spend(Origin::none(), tx.clone()),
+            Error::<Test>::InvalidWitness
+        );
+
+        tx.inputs[0].witness = vec![alice_sig.0.to_vec()];
+        assert_ok!(Utxo::spend(Origin::none(), tx));
+    })
+}
+
+#[test]
+fn attack_with_oversized_witness() {
+    execute_with_alice(|alice_pub_key| {
+        let mut tx = Transaction {
+            inputs: vec![tx_input_gen_no_signature()],
+            outputs: vec![TransactionOutput::new(50, H256::from(alice_pub_key))],
+        };
+
+        tx.inputs[0].witness = vec![vec![]; crate::MAX_WITNESS_ITEMS + 1];
+        assert_noop!(
+            Utxo::spend(Origin::none(), tx.clone()),
+            Error::<Test>::WitnessTooLarge
+        );
+
+        tx.inputs[0].witness = vec![vec![0; crate::MAX_WITNESS_SIZE + 1]];
+        assert_noop!(
+            Utxo::spend(Origin::none(), tx.clone()),
+            Error::<Test>::WitnessTooLarge
+        );
+        assert_eq!(tx.witness_size(), crate::MAX_WITNESS_SIZE + 1);
+    })
+}
+
+#[test]
+fn test_batch_verification() {
+    execute_with_alice(|alice_pub_key| {
+        let mut tx = Transaction {
+            inputs: vec![tx_input_gen_no_signature()],
+            outputs: vec![TransactionOutput::new(50, H256::from(alice_pub_key))],
+        };
+        let alice_sig =
+            crypto::sr25519_sign(SR25519, &alice_pub_key, &sig_message(&tx, 0)).unwrap();
+        tx.inputs[0].witness = vec![alice_sig.0.to_vec()];
+
+        crypto::start_batch_verify();
+        assert_ok!(Utxo::spend(Origin::none(), tx));
+        assert!(crypto::finish_batch_verify());
+    })
+}
+
+#[test]
+fn attack_with_invalid_signature_in_batch() {
+    execute_with_alice(|alice_pub_key| {
+        let mut tx = Transaction {
+            inputs: vec![tx_input_gen_no_signature()],
+            outputs: vec![TransactionOutput::new(50, H256::from(alice_pub_key))],
+        };
+        tx.inputs[0].witness = vec![H512::random().as_bytes().to_vec()];
+
+        // within a batch the signature is only checked once the batch is finished,
+        // which fails the whole block
+        crypto::start_batch_verify();
+        assert_ok!(Utxo::spend(Origin::none(), tx));
+        assert!(!crypto::finish_batch_verify());
+    })
+}
+
+// Alice, karl and a third key lock a 2-of-3 multisig utxo, spent back to alice.
+fn execute_with_multisig<F>(mut execute: F)
+where
+    F: FnMut(Transaction, [Public; 3]),
+{
+    execute_with_alice(|alice_pub_key| {
+        let karl_pub_key = crypto::sr25519_generate(SR25519, None);
+        let other_pub_key = crypto::sr25519_generate(SR25519, None);
+        let keys = [alice_pub_key, karl_pub_key, other_pub_key];
+
+        let utxo = TransactionOutput::new_multisig(
+            100,
+            2,
+            keys.iter().map(|key| H256::from(*key)).collect(),
+        );
+        let outpoint = OutPoint::new(H256::random(), 0);
+        UtxoStore::<Test>::insert(outpoint, Some(utxo));
+
+        let tx = Transaction {
+            inputs: vec![TransactionInput::new(outpoint, vec![])],
+            outputs: vec![TransactionOutput::new(100, H256::from(alice_pub_key))],
+        };
+        execute(tx, keys);
+    })
+}
+
+// A multisig witness item: the index of the key, then its signature of the input.
+fn multisig_item(tx: &Transaction, index: u8, pub_key: &Public) -> Vec<u8> {
+    let signature = crypto::sr25519_sign(SR25519, pub_key, &sig_message(tx, 0)).unwrap();
+    let mut item = vec![index];
+    item.extend_from_slice(&signature.0);
+    item
+}
+
+#[test]
+fn test_multisig() {
+    execute_with_multisig(|mut tx, keys| {
+        tx.inputs[0].witness = vec![
+            multisig_item(&tx, 2, &keys[2]),
+            multisig_item(&tx, 0, &keys[0]),
+        ];
+        assert_ok!(Utxo::spend(Origin::none(), tx));
+    })
+}
+
+#[test]
+fn attack_with_under_signed_multisig() {
+    execute_with_multisig(|mut tx, keys| {
+        tx.inputs[0].witness = vec![multisig_item(&tx, 1, &keys[1])];
+        assert_noop!(
+            Utxo::spend(Origin::none(), tx),
+            Error::<Test>::ThresholdNotMet
+        );
+    })
+}
+
+#[test]
+fn attack_with_duplicate_multisig_signer() {
+    execute_with_multisig(|mut tx, keys| {
+        // sr25519 signatures are randomised, so the two items differ
+        tx.inputs[0].witness = vec![
+            multisig_item(&tx, 1, &keys[1]),
+            multisig_item(&tx, 1, &keys[1]),
+        ];
+        assert_noop!(
+            Utxo::spend(Origin::none(), tx),
+            Error::<Test>::DuplicateSigner
+        );
+    })
+}
+
+#[test]
+fn attack_with_wrong_multisig_key() {
+    execute_with_multisig(|mut tx, keys| {
+        // karl signs in place of the third key
+        tx.inputs[0].witness = vec![
+            multisig_item(&tx, 0, &keys[0]),
+            multisig_item(&tx, 2, &keys[1]),
+        ];
+        assert_noop!(
+            Utxo::spend(Origin::none(), tx.clone()),
+            Error::<Test>::SignatureFailure
+        );
+
+        // a key index outside of the destination
+        tx.inputs[0].witness = vec![
+            multisig_item(&tx, 0, &keys[0]),
+            multisig_item(&tx, 3, &keys[1]),
+        ];
+        assert_noop!(
+            Utxo::spend(Origin::none(), tx),
+            Error::<Test>::InvalidWitness
+        );
+    })
+}
+
+#[test]
+fn attack_with_invalid_multisig_destination() {
+    execute_with_alice(|alice_pub_key| {
+        let alice_h256 = H256::from(alice_pub_key);
+        let invalid_outputs = vec![
+            TransactionOutput::new_multisig(50, 0, vec![alice_h256]),
+            TransactionOutput::new_multisig(50, 2, vec![alice_h256]),
+            // a repeated key would let one signer count twice
+            TransactionOutput::new_multisig(50, 2, vec![alice_h256, alice_h256]),
+            TransactionOutput::new_multisig(
+                50,
+                1,
+                (0..=MAX_MULTISIG_KEYS).map(|_| H256::random()).collect(),
+            ),
+        ];
+
+        for output in invalid_outputs {
+            let mut tx = Transaction {
+                inputs: vec![tx_input_gen_no_signature()],
+                outputs: vec![output],
+            };
+            let alice_sig =
+                crypto::sr25519_sign(SR25519, &alice_pub_key, &sig_message(&tx, 0)).unwrap();
+            tx.inputs[0].witness = vec![alice_sig.0.to_vec()];
+            assert_noop!(
+                Utxo::spend(Origin::none(), tx),
+                Error::<Test>::InvalidDestination
+            );
+        }
+    })
+}
+
+// Spends to alice a utxo of hers under `timelock`.
+fn timelocked_tx(alice_pub_key: Public, timelock: Timelock) -> Transaction {
+    let utxo = TransactionOutput::new(100, H256::from(alice_pub_key)).with_timelock(timelock);
+    let outpoint = OutPoint::new(H256::random(), 0);
+    UtxoStore::<Test>::insert(outpoint, Some(utxo));
+
+    let mut tx = Transaction {
+        inputs: vec![TransactionInput::new(outpoint, vec![])],
+        outputs: vec![TransactionOutput::new(100, H256::from(alice_pub_key))],
+    };
+    let alice_sig = crypto::sr25519_sign(SR25519, &alice_pub_key, &sig_message(&tx, 0)).unwrap();
+    tx.inputs[0].witness = vec![alice_sig.0.to_vec()];
+    tx
+}
+
+#[test]
+fn test_block_timelock() {
+    execute_with_alice(|alice_pub_key| {
+        let timelock = Timelock::UntilBlock(10);
+        let tx = timelocked_tx(alice_pub_key, timelock);
+
+        System::set_block_number(7);
+        let valid_tx = crate::validate_transaction::<Test>(&tx).unwrap();
+        assert_eq!(valid_tx.requires, vec![(b"timelock", timelock).encode()]);
+        assert_eq!(valid_tx.longevity, 3);
+        assert_noop!(
+            Utxo::spend(Origin::none(), tx.clone()),
+            Error::<Test>::TimelockNotExpired
+        );
+
+        System::set_block_number(10);
+        let valid_tx = crate::validate_transaction::<Test>(&tx).unwrap();
+        assert!(valid_tx.requires.is_empty());
+        assert_eq!(valid_tx.longevity, TransactionLongevity::MAX);
+        assert_ok!(Utxo::spend(Origin::none(), tx));
+    })
+}
+
+#[test]
+fn test_premature_spend_in_pool() {
+    execute_with_alice(|alice_pub_key| {
+        // the pool keeps the transaction until the lock expires, but a block author skips it
+        let timelock = Timelock::UntilBlock(10);
+        let tx = timelocked_tx(alice_pub_key, timelock);
+        let call = UtxoCall::spend(tx);
+        let valid_tx = Utxo::validate_unsigned(TransactionSource::External, &call).unwrap();
+        assert_eq!(valid_tx.requires, vec![(b"timelock", timelock).encode()]);
+        assert_eq!(valid_tx.longevity, 10);
+        assert_eq!(
+            Utxo::pre_dispatch(&call),
+            Err(InvalidTransaction::Future.into())
+        );
+
+        System::set_block_number(10);
+        assert_ok!(Utxo::validate_unsigned(TransactionSource::External, &call));
+    })
+}
+
+#[test]
+fn attack_with_far_off_timelock() {
+    execute_with_alice(|alice_pub_key| {
+        // the transaction cannot wait in the pool for ever
+        let tx = timelocked_tx(alice_pub_key, Timelock::UntilBlock(u64::MAX));
+        let valid_tx = crate::validate_transaction::<Test>(&tx).unwrap();
+        assert_eq!(valid_tx.longevity, MAX_TIMELOCK_LONGEVITY);
+
+        // nor for a time lock, which is not queued at all
+        let tx = timelocked_tx(alice_pub_key, Timelock::UntilTime(u64::MAX));
+        assert!(matches!(
+            crate::validate_transaction::<Test>(&tx),
+            Err(Error::<Test>::TimelockNotExpired)
+        ));
+    })
+}
+
+#[test]
+fn test_time_timelock() {
+    execute_with_alice(|alice_pub_key| {
+        let timelock = Timelock::UntilTime(1_600_000_000);
+        let tx = timelocked_tx(alice_pub_key, timelock);
+
+        // the timestamp pallet counts milliseconds. Just before the lock expires, the pool is
+        // told to come back later rather than to queue the transaction.
+        Timestamp::set_timestamp(1_599_999_999_000);
+        let call = UtxoCall::spend(tx.clone());
+        assert_eq!(
+            Utxo::validate_unsigned(TransactionSource::External, &call),
+            Err(InvalidTransaction::Future.into())
+        );
+        assert_noop!(
+            Utxo::spend(Origin::none(), tx.clone()),
+            Error::<Test>::TimelockNotExpired
+        );
+
+        Timestamp::set_timestamp(1_600_000_000_000);
+        assert_ok!(Utxo::validate_unsigned(TransactionSource::External, &call));
+        assert_ok!(Utxo::spend(Origin::none(), tx));
+    })
+}
+
+#[test]
+fn attack_with_missing_input_and_timelock() {
+    execute_with_alice(|alice_pub_key| {
+        let mut tx = timelocked_tx(alice_pub_key, Timelock::UntilBlock(10));
+        tx.inputs.push(TransactionInput::new(
+            OutPoint::new(H256::random(), 0),
+            vec![],
+        ));
+        let alice_sig =
+            crypto::sr25519_sign(SR25519, &alice_pub_key, &sig_message(&tx, 0)).unwrap();
+        tx.inputs[0].witness = vec![alice_sig.0.to_vec()];
+
+        // a missing input takes precedence over the lock
+        assert_noop!(
+            Utxo::spend(Origin::none(), tx),
+            Error::<Test>::MissingInputs
+        );
+    })
+}
+
+#[test]
+fn test_relative_timelock() {
+    execute_with_alice(|alice_pub_key| {
+        // the locked utxo is created in block 5
+        System::set_block_number(5);
+        let mut tx = Transaction {
+            inputs: vec![tx_input_gen_no_signature()],
+            outputs: vec![TransactionOutput::new(100, H256::from(alice_pub_key))
+                .with_timelock(Timelock::AfterBlocks(3))],
+        };
+        let alice_sig =
+            crypto::sr25519_sign(SR25519, &alice_pub_key, &sig_message(&tx, 0)).unwrap();
+        tx.inputs[0].witness = vec![alice_sig.0.to_vec()];
+        assert_ok!(Utxo::spend(Origin::none(), tx.clone()));
+        assert_eq!(UtxoCreation::<Test>::get(tx.outpoint(0)), Some(5));
+
+        let mut spend_tx = Transaction {
+            inputs: vec![TransactionInput::new(tx.outpoint(0), vec![])],
+            outputs: vec![TransactionOutput::new(100, H256::from(alice_pub_key))],
+        };
+        let alice_sig =
+            crypto::sr25519_sign(SR25519, &alice_pub_key, &sig_message(&spend_tx, 0)).unwrap();
+        spend_tx.inputs[0].witness = vec![alice_sig.0.to_vec()];
+
+        System::set_block_number(7);
+        assert_noop!(
+            Utxo::spend(Origin::none(), spend_tx.clone()),
+            Error::<Test>::TimelockNotExpired
+        );
+
+        System::set_block_number(8);
+        assert_ok!(Utxo::spend(Origin::none(), spend_tx.clone()));
+        assert_eq!(UtxoCreation::<Test>::get(tx.outpoint(0)), None);
+        assert_eq!(UtxoCreation::<Test>::get(spend_tx.outpoint(0)), Some(8));
+    })
+}
+
+#[test]
+fn test_migrate_creation_blocks() {
+    execute_with_alice(|alice_pub_key| {
+        assert_eq!(UtxoCreation::<Test>::get(genesis_outpoint()), Some(0));
+
+        // utxos stored before creation blocks were recorded, in the layout before NFTs
+        let header = TransactionOutput::new(20, H256::from(alice_pub_key)).header;
+        let genesis = UtxoStore::<Test>::get(genesis_outpoint()).unwrap();
+        let genesis_v6 = migrations::TransactionOutputV6 {
+            value: genesis.value,
+            destination: genesis.destination.clone(),
+            header: genesis.header,
+            timelock: None,
+        };
+        unhashed::put(
+            &UtxoStore::<Test>::hashed_key_for(genesis_outpoint()),
+            &Some(genesis_v6),
+        );
+        let utxo = migrations::TransactionOutputV6 {
+            value: 20,
+            destination: Destination::Pubkey(H256::from(alice_pub_key)),
+            header,
+            timelock: Some(Timelock::AfterBlocks(3)),
+        };
+        let outpoint = OutPoint::new(H256::random(), 0);
+        unhashed::put(&UtxoStore::<Test>::hashed_key_for(outpoint), &Some(utxo));
+        StorageVersion::<Test>::put(Releases::V5);
+        System::set_block_number(42);
+
+        migrations::migrate::<Test>();
+
+        assert_eq!(StorageVersion::<Test>::get(), Releases::V8);
+        assert_eq!(UtxoCreation::<Test>::get(outpoint), Some(42));
+        assert_eq!(
+            UtxoStore::<Test>::get(outpoint),
+            Some(
+                TransactionOutput::new(20, H256::from(alice_pub_key))
+                    .with_timelock(Timelock::AfterBlocks(3))
+            )
+        );
+        // known creation blocks are kept
+        assert_eq!(UtxoCreation::<Test>::get(genesis_outpoint()), Some(0));
+        assert_eq!(UtxoStore::<Test>::get(genesis_outpoint()), Some(genesis));
+    })
+}
+
+#[test]
+fn test_migrated_relative_timelock() {
+    execute_with_alice(|alice_pub_key| {
+        // a relatively locked utxo stored at `Releases::V5`, whose creation block is unknown
+        UtxoStore::<Test>::remove(genesis_outpoint());
+        let utxo = migrations::TransactionOutputV6 {
+            value: 100,
+            destination: Destination::Pubkey(H256::from(alice_pub_key)),
+            header: TransactionOutput::new(100, H256::from(alice_pub_key)).header,
+            timelock: Some(Timelock::AfterBlocks(3)),
+        };
+        let outpoint = OutPoint::new(H256::random(), 0);
+        unhashed::put(&UtxoStore::<Test>::hashed_key_for(outpoint), &Some(utxo));
+        StorageVersion::<Test>::put(Releases::V5);
+        System::set_block_number(42);
+
+        migrations::migrate::<Test>();
+
+        // the lock runs from the block of the migration
+        let mut spend_tx = Transaction {
+            inputs: vec![TransactionInput::new(outpoint, vec![])],
+            outputs: vec![TransactionOutput::new(100, H256::from(alice_pub_key))],
+        };
+        let alice_sig =
+            crypto::sr25519_sign(SR25519, &alice_pub_key, &sig_message(&spend_tx, 0)).unwrap();
+        spend_tx.inputs[0].witness = vec![alice_sig.0.to_vec()];
+
+        System::set_block_number(44);
+        assert_noop!(
+            Utxo::spend(Origin::none(), spend_tx.clone()),
+            Error::<Test>::TimelockNotExpired
+        );
+
+        System::set_block_number(45);
+        assert_ok!(Utxo::spend(Origin::none(), spend_tx.clone()));
+        assert_eq!(UtxoCreation::<Test>::get(outpoint), None);
+    })
+}
+
+#[test]
+fn test_migrate_nfts() {
+    execute_with_alice(|alice_pub_key| {
+        // a timelocked utxo stored before NFTs
+        UtxoStore::<Test>::remove(genesis_outpoint());
+        let utxo = migrations::TransactionOutputV6 {
+            value: 20,
+            destination: Destination::Pubkey(H256::from(alice_pub_key)),
+            header: TransactionOutput::new(20, H256::from(alice_pub_key)).header,
+            timelock: Some(Timelock::UntilBlock(5)),
+        };
+        let outpoint = OutPoint::new(H256::random(), 0);
+        unhashed::put(&UtxoStore::<Test>::hashed_key_for(outpoint), &Some(utxo));
+        StorageVersion::<Test>::put(Releases::V6);
+
+        migrations::migrate::<Test>();
+
+        assert_eq!(StorageVersion::<Test>::get(), Releases::V8);
+        assert_eq!(
+            UtxoStore::<Test>::get(outpoint),
+            Some(
+                TransactionOutput::new(20, H256::from(alice_pub_key))
+                    .with_timelock(Timelock::UntilBlock(5))
+            )
+        );
+    })
+}
+
+#[test]
+fn test_migrate_token_rewards() {
+    execute_with_alice(|_| {
+        // the reward total stored as a single value, of MLT
+        unhashed::put::<Value>(&RewardTotal::<Test>::final_prefix(), &25);
+        StorageVersion::<Test>::put(Releases::V7);
+
+        migrations::migrate::<Test>();
+
+        assert_eq!(StorageVersion::<Test>::get(), Releases::V8);
+        assert_eq!(RewardTotal::<Test>::get(TokenType::MLT as TokenID), 25);
+        assert_eq!(RewardTotal::<Test>::iter().count(), 1);
+    })
+}
+
+const HTLC_PREIMAGE: &[u8] = b"the secret of the swap";
+
+// Karl locks an htlc to alice, refundable to him from block 10, and alice's key spends it.
+fn execute_with_htlc<F>(mut execute: F)
+where
+    F: FnMut(Transaction, Public, Public),
+{
+    execute_with_alice(|alice_pub_key| {
+        let karl_pub_key = crypto::sr25519_generate(SR25519, None);
+        let utxo = TransactionOutput::new_htlc(
+            100,
+            H256::from(alice_pub_key),
+            H256::from(karl_pub_key),
+            H256::from(sha2_256(HTLC_PREIMAGE)),
+            10,
+        );
+        let outpoint = OutPoint::new(H256::random(), 0);
+        UtxoStore::<Test>::insert(outpoint, Some(utxo));
+
+        let tx = Transaction {
+            inputs: vec![TransactionInput::new(outpoint, vec![])],
+            outputs: vec![TransactionOutput::new(100, H256::from(alice_pub_key))],
+        };
+        execute(tx, alice_pub_key, karl_pub_key);
+    })
+}
+
+#[test]
+fn test_htlc_claim() {
+    execute_with_htlc(|mut tx, alice_pub_key, _| {
+        let alice_sig =
+            crypto::sr25519_sign(SR25519, &alice_pub_key, &sig_message(&tx, 0)).unwrap();
+        tx.inputs[0].witness = vec![alice_sig.0.to_vec(), HTLC_PREIMAGE.to_vec()];
+        assert_ok!(Utxo::spend(Origin::none(), tx));
+    })
+}
+
+#[test]
+fn test_htlc_refund() {
+    execute_with_htlc(|mut tx, _, karl_pub_key| {
+        let karl_sig = crypto::sr25519_sign(SR25519, &karl_pub_key, &sig_message(&tx, 0)).unwrap();
+        tx.inputs[0].witness = vec![karl_sig.0.to_vec()];
+
+        System::set_block_number(10);
+        assert_ok!(Utxo::spend(Origin::none(), tx));
+    })
+}
+
+#[test]
+fn attack_with_wrong_htlc_preimage() {
+    execute_with_htlc(|mut tx, alice_pub_key, karl_pub_key| {
+        let alice_sig =
+            crypto::sr25519_sign(SR25519, &alice_pub_key, &sig_message(&tx, 0)).unwrap();
+        tx.inputs[0].witness = vec![alice_sig.0.to_vec(), b"a guess".to_vec()];
+        assert_noop!(
+            Utxo::spend(Origin::none(), tx.clone()),
+            Error::<Test>::InvalidPreimage
+        );
+
+        // the preimage does not let the refund key claim
+        let karl_sig = crypto::sr25519_sign(SR25519, &karl_pub_key, &sig_message(&tx, 0)).unwrap();
+        tx.inputs[0].witness = vec![karl_sig.0.to_vec(), HTLC_PREIMAGE.to_vec()];
+        assert_noop!(
+            Utxo::spend(Origin::none(), tx),
+            Error::<Test>::SignatureFailure
+        );
+    })
+}
+
+#[test]
+fn attack_with_early_htlc_refund() {
+    execute_with_htlc(|mut tx, alice_pub_key, karl_pub_key| {
+        let karl_sig = crypto::sr25519_sign(SR25519, &karl_pub_key, &sig_message(&tx, 0)).unwrap();
+        tx.inputs[0].witness = vec![karl_sig.0.to_vec()];
+
+        System::set_block_number(9);
+        assert_noop!(
+            Utxo::spend(Origin::none(), tx.clone()),
+            Error::<Test>::TimelockNotExpired
+        );
+
+        // the recipient may not take the refund path either
+        let alice_sig =
+            crypto::sr25519_sign(SR25519, &alice_pub_key, &sig_message(&tx, 0)).unwrap();
+        tx.inputs[0].witness = vec![alice_sig.0.to_vec()];
+        System::set_block_number(10);
+        assert_noop!(
+            Utxo::spend(Origin::none(), tx),
+            Error::<Test>::SignatureFailure
+        );
+    })
+}
+
+// Spends to alice a utxo locked to `script`.
+fn script_tx(alice_pub_key: Public, script: Vec<Op>) -> Transaction {
+    let outpoint = OutPoint::new(H256::random(), 0);
+    UtxoStore::<Test>::insert(outpoint, Some(TransactionOutput::new_script(100, script)));
+
+    Transaction {
+        inputs: vec![TransactionInput::new(outpoint, vec![])],
+        outputs: vec![TransactionOutput::new(100, H256::from(alice_pub_key))],
+    }
+}
+
+#[test]
+fn test_script() {
+    execute_with_alice(|alice_pub_key| {
+        // alice alone, or karl after block 10
+        let karl_pub_key = crypto::sr25519_generate(SR25519, None);
+        let script = vec![
+            Op::If,
+            Op::key(H256::from(alice_pub_key)),
+            Op::Else,
+            Op::number(10),
+            Op::CheckLockTimeVerify,
+            Op::Drop,
+            Op::key(H256::from(karl_pub_key)),
+            Op::EndIf,
+            Op::CheckSig,
+        ];
+
+        let mut tx = script_tx(alice_pub_key, script.clone());
+        let alice_sig =
+            crypto::sr25519_sign(SR25519, &alice_pub_key, &sig_message(&tx, 0)).unwrap();
+        tx.inputs[0].witness = vec![alice_sig.0.to_vec(), vec![1]];
+        assert_ok!(Utxo::spend(Origin::none(), tx));
+
+        let mut tx = script_tx(alice_pub_key, script);
+        let karl_sig = crypto::sr25519_sign(SR25519, &karl_pub_key, &sig_message(&tx, 0)).unwrap();
+        tx.inputs[0].witness = vec![karl_sig.0.to_vec(), vec![]];
+        assert_noop!(
+            Utxo::spend(Origin::none(), tx.clone()),
+            Error::<Test>::TimelockNotExpired
+        );
+        System::set_block_number(10);
+        assert_ok!(Utxo::spend(Origin::none(), tx));
+    })
+}
+
+#[test]
+fn test_script_time_lock() {
+    execute_with_alice(|alice_pub_key| {
+        // alice, once the chain's time reaches 1_600_000_000
+        let script = vec![
+            Op::number(1_600_000_000),
+            Op::CheckLockTimestampVerify,
+            Op::Drop,
+            Op::key(H256::from(alice_pub_key)),
+            Op::CheckSig,
+        ];
+        let mut tx = script_tx(alice_pub_key, script);
+        let alice_sig =
+            crypto::sr25519_sign(SR25519, &alice_pub_key, &sig_message(&tx, 0)).unwrap();
+        tx.inputs[0].witness = vec![alice_sig.0.to_vec()];
+
+        // the timestamp pallet counts milliseconds
+        Timestamp::set_timestamp(1_599_999_999_000);
+        assert_noop!(
+            Utxo::spend(Origin::none(), tx.clone()),
+            Error::<Test>::TimelockNotExpired
+        );
+        Timestamp::set_timestamp(1_600_000_000_000);
+        assert_ok!(Utxo::spend(Origin::none(), tx));
+    })
+}
+
+#[test]
+fn test_multisig_script_in_batch() {
+    execute_with_alice(|alice_pub_key| {
+        let karl_pub_key = crypto::sr25519_generate(SR25519, None);
+        let other_pub_key = crypto::sr25519_generate(SR25519, None);
+        let script = vec![
+            Op::number(2),
+            Op::key(H256::from(alice_pub_key)),
+            Op::key(H256::from(karl_pub_key)),
+            Op::key(H256::from(other_pub_key)),
+            Op::number(3),
+            Op::CheckMultisigVerify,
+            Op::number(1),
+        ];
+
+        // the first and the third key sign, as during block import
+        let mut tx = script_tx(alice_pub_key, script);
+        let alice_sig =
+            crypto::sr25519_sign(SR25519, &alice_pub_key, &sig_message(&tx, 0)).unwrap();
+        let other_sig =
+            crypto::sr25519_sign(SR25519, &other_pub_key, &sig_message(&tx, 0)).unwrap();
+        tx.inputs[0].witness = vec![alice_sig.0.to_vec(), vec![], other_sig.0.to_vec()];
+
+        crypto::start_batch_verify();
+        assert_ok!(Utxo::spend(Origin::none(), tx));
+        assert!(crypto::finish_batch_verify());
+    })
+}
+
+#[test]
+fn attack_with_failing_script() {
+    execute_with_alice(|alice_pub_key| {
+        let karl_pub_key = crypto::sr25519_generate(SR25519, None);
+        let script = vec![Op::key(H256::from(alice_pub_key)), Op::CheckSig];
+
+        let mut tx = script_tx(alice_pub_key, script);
+        tx.inputs[0].witness = vec![vec![]];
+        assert_noop!(
+            Utxo::spend(Origin::none(), tx.clone()),
+            Error::<Test>::ScriptFailed
+        );
+
+        let karl_sig = crypto::sr25519_sign(SR25519, &karl_pub_key, &sig_message(&tx, 0)).unwrap();
+        tx.inputs[0].witness = vec![karl_sig.0.to_vec()];
+        assert_noop!(
+            Utxo::spend(Origin::none(), tx),
+            Error::<Test>::SignatureFailure
+        );
+    })
+}
+
+#[test]
+fn attack_with_oversized_script_destination() {
+    execute_with_alice(|alice_pub_key| {
+        let mut tx = Transaction {
+            inputs: vec![tx_input_gen_no_signature()],
+            outputs: vec![TransactionOutput::new_script(
+                50,
+                vec![Op::Dup; MAX_SCRIPT_OPS + 1],
+            )],
+        };
+        let alice_sig =
+            crypto::sr25519_sign(SR25519, &alice_pub_key, &sig_message(&tx, 0)).unwrap();
+        tx.inputs[0].witness = vec![alice_sig.0.to_vec()];
+        assert_noop!(
+            Utxo::spend(Origin::none(), tx),
+            Error::<Test>::InvalidDestination
+        );
+    })
+}
+
+// Spends to alice a utxo committing to `policy`.
+fn script_hash_tx(alice_pub_key: Public, policy: &Destination) -> Transaction {
+    let outpoint = OutPoint::new(H256::random(), 0);
+    let utxo = TransactionOutput::new_script_hash(100, policy);
+    UtxoStore::<Test>::insert(outpoint, Some(utxo));
+
+    Transaction {
+        inputs: vec![TransactionInput::new(outpoint, vec![])],
+        outputs: vec![TransactionOutput::new(100, H256::from(alice_pub_key))],
+    }
+}
+
+#[test]
+fn test_script_hash() {
+    execute_with_alice(|alice_pub_key| {
+        let karl_pub_key = crypto::sr25519_generate(SR25519, None);
+
+        // a 2-of-2 multisig policy
+        let policy = Destination::Multisig {
+            threshold: 2,
+            keys: vec![H256::from(alice_pub_key), H256::from(karl_pub_key)],
+        };
+        let mut tx = script_hash_tx(alice_pub_key, &policy);
+        tx.inputs[0].witness = vec![
+            multisig_item(&tx, 0, &alice_pub_key),
+            multisig_item(&tx, 1, &karl_pub_key),
+            policy.encode(),
+        ];
+        assert_ok!(Utxo::spend(Origin::none(), tx));
+
+        // a script policy
+        let policy = Destination::Script(vec![Op::key(H256::from(karl_pub_key)), Op::CheckSig]);
+        let mut tx = script_hash_tx(alice_pub_key, &policy);
+        let karl_sig = crypto::sr25519_sign(SR25519, &karl_pub_key, &sig_message(&tx, 0)).unwrap();
+        tx.inputs[0].witness = vec![karl_sig.0.to_vec(), policy.encode()];
+        assert_ok!(Utxo::spend(Origin::none(), tx));
+    })
+}
+
+#[test]
+fn attack_with_wrong_script_hash_policy() {
+    execute_with_alice(|alice_pub_key| {
+        let karl_pub_key = crypto::sr25519_generate(SR25519, None);
+        let policy = Destination::Pubkey(H256::from(karl_pub_key));
+        let mut tx = script_hash_tx(alice_pub_key, &policy);
+        let alice_sig =
+            crypto::sr25519_sign(SR25519, &alice_pub_key, &sig_message(&tx, 0)).unwrap();
+
+        // alice cannot swap in a policy of her own
+        let alice_policy = Destination::Pubkey(H256::from(alice_pub_key));
+        tx.inputs[0].witness = vec![alice_sig.0.to_vec(), alice_policy.encode()];
+        assert_noop!(
+            Utxo::spend(Origin::none(), tx.clone()),
+            Error::<Test>::InvalidPolicy
+        );
+
+        // the policy has to be revealed
+        tx.inputs[0].witness = vec![];
+        assert_noop!(
+            Utxo::spend(Origin::none(), tx.clone()),
+            Error::<Test>::InvalidWitness
+        );
+
+        // the revealed policy is then enforced
+        tx.inputs[0].witness = vec![alice_sig.0.to_vec(), policy.encode()];
+        assert_noop!(
+            Utxo::spend(Origin::none(), tx),
+            Error::<Test>::SignatureFailure
+        );
+    })
+}
+
+#[test]
+fn attack_with_nested_script_hash() {
+    execute_with_alice(|alice_pub_key| {
+        let inner = Destination::Pubkey(H256::from(alice_pub_key));
+        let policy = Destination::ScriptHash(inner.script_hash());
+        let mut tx = script_hash_tx(alice_pub_key, &policy);
+        let alice_sig =
+            crypto::sr25519_sign(SR25519, &alice_pub_key, &sig_message(&tx, 0)).unwrap();
+        tx.inputs[0].witness = vec![alice_sig.0.to_vec(), inner.encode(), policy.encode()];
+        assert_noop!(
+            Utxo::spend(Origin::none(), tx),
+            Error::<Test>::InvalidPolicy
+        );
+    })
+}
+
+// An internal key known outside of the keystore, to tweak its secret.
+fn taproot_keypair() -> schnorrkel::Keypair {
+    schnorrkel::MiniSecretKey::from_bytes(&[42; 32])
+        .unwrap()
+        .expand_to_keypair(schnorrkel::ExpansionMode::Ed25519)
+}
+
+// Spends to alice a taproot utxo of the internal key and the policies' root.
+fn taproot_tx(
+    alice_pub_key: Public,
+    internal_key: &H256,
+    merkle_root: Option<H256>,
+) -> Transaction {
+    let outpoint = OutPoint::new(H256::random(), 0);
+    let utxo = TransactionOutput::new_taproot(100, internal_key, merkle_root).unwrap();
+    UtxoStore::<Test>::insert(outpoint, Some(utxo));
+
+    Transaction {
+        inputs: vec![TransactionInput::new(outpoint, vec![])],
+        outputs: vec![TransactionOutput::new(100, H256::from(alice_pub_key))],
+    }
+}
+
+#[test]
+fn test_taproot_key_path() {
+    execute_with_alice(|alice_pub_key| {
+        let keypair = taproot_keypair();
+        let internal_key = H256(keypair.public.to_bytes());
+        let root = Some(H256::repeat_byte(1));
+        let mut tx = taproot_tx(alice_pub_key, &internal_key, root);
+
+        // the internal key alone does not sign for the output
+        let context = schnorrkel::signing_context(b"substrate");
+        let message = sig_message(&tx, 0);
+        let signature = keypair.sign(context.bytes(&message));
+        tx.inputs[0].witness = vec![signature.to_bytes().to_vec()];
+        assert_noop!(
+            Utxo::spend(Origin::none(), tx.clone()),
+            Error::<Test>::SignatureFailure
+        );
+
+        let secret_key = taproot::tweak_secret_key(&keypair.secret, root);
+        let signature = secret_key.sign(context.bytes(&message), &secret_key.to_public());
+        tx.inputs[0].witness = vec![signature.to_bytes().to_vec()];
+        assert_ok!(Utxo::spend(Origin::none(), tx));
+    })
+}
+
+#[test]
+fn test_taproot_script_path() {
+    execute_with_alice(|alice_pub_key| {
+        let internal_key = H256(taproot_keypair().public.to_bytes());
+        let karl_pub_key = crypto::sr25519_generate(SR25519, None);
+        let alice_policy = Destination::Pubkey(H256::from(alice_pub_key));
+        let karl_policy = Destination::Pubkey(H256::from(karl_pub_key));
+        let root = taproot::branch_hash(
+            &taproot::leaf_hash(&alice_policy),
+            &taproot::leaf_hash(&karl_policy),
+        );
+
+        let mut tx = taproot_tx(alice_pub_key, &internal_key, Some(root));
+        let alice_sig =
+            crypto::sr25519_sign(SR25519, &alice_pub_key, &sig_message(&tx, 0)).unwrap();
+        let control = ControlBlock {
+            internal_key,
+            path: vec![taproot::leaf_hash(&karl_policy)],
+        };
+
+        // alice cannot pass her policy off as karl's
+        tx.inputs[0].witness = vec![
+            alice_sig.0.to_vec(),
+            alice_policy.encode(),
+            ControlBlock {
+                internal_key,
+                path: vec![taproot::leaf_hash(&alice_policy)],
+            }
+            .encode(),
+        ];
+        assert_noop!(
+            Utxo::spend(Origin::none(), tx.clone()),
+            Error::<Test>::InvalidPolicy
+        );
+
+        tx.inputs[0].witness = vec![
+            alice_sig.0.to_vec(),
+            alice_policy.encode(),
+            control.encode(),
+        ];
+        assert_ok!(Utxo::spend(Origin::none(), tx));
+    })
+}
+
+#[test]
+fn attack_with_non_schnorr_taproot() {
+    execute_with_alice(|alice_pub_key| {
+        let internal_key = H256(taproot_keypair().public.to_bytes());
+        let mut output = TransactionOutput::new_taproot(50, &internal_key, None).unwrap();
+        let mut header = OutputHeader::new(output.header);
+        header.set_sign_method(SignatureMethod::Ecdsa);
+        output.header = header.as_u128();
+
+        let mut tx = Transaction {
+            inputs: vec![tx_input_gen_no_signature()],
+            outputs: vec![output],
+        };
+        let alice_sig =
+            crypto::sr25519_sign(SR25519, &alice_pub_key, &sig_message(&tx, 0)).unwrap();
+        tx.inputs[0].witness = vec![alice_sig.0.to_vec()];
+        assert_noop!(
+            Utxo::spend(Origin::none(), tx),
+            Error::<Test>::InvalidDestination
+        );
+    })
+}
+
+// Three federation members, known outside of the keystore to run the MuSig2 rounds.
+fn federation() -> Vec<schnorrkel::Keypair> {
+    (1..=3u8)
+        .map(|seed| {
+            schnorrkel::MiniSecretKey::from_bytes(&[seed; 32])
+                .unwrap()
+                .expand_to_keypair(schnorrkel::ExpansionMode::Ed25519)
+        })
+        .collect()
+}
+
+#[test]
+fn test_musig_aggregate_key() {
+    execute_with_alice(|alice_pub_key| {
+        let members = federation();
+        let keys: Vec<H256> = members
+            .iter()
+            .map(|member| H256(member.public.to_bytes()))
+            .collect();
+        let aggregate_key = musig::aggregate_key(&keys).unwrap();
+        let reversed_keys: Vec<H256> = keys.iter().rev().cloned().collect();
+        assert_eq!(musig::aggregate_key(&reversed_keys), Some(aggregate_key));
+
+        // the federation's utxo looks like any single key's
+        let outpoint = OutPoint::new(H256::random(), 0);
+        UtxoStore::<Test>::insert(outpoint, Some(TransactionOutput::new(100, aggregate_key)));
+        let mut tx = Transaction {
+            inputs: vec![TransactionInput::new(outpoint, vec![])],
+            outputs: vec![TransactionOutput::new(100, H256::from(alice_pub_key))],
+        };
+
+        let signature = musig::sign(&members, &sig_message(&tx, 0)).unwrap();
+        tx.inputs[0].witness = vec![signature.as_bytes().to_vec()];
+        assert_ok!(Utxo::spend(Origin::none(), tx));
+    })
+}
+
+#[test]
+fn test_musig_rounds_apart() {
+    execute_with_alice(|alice_pub_key| {
+        let members = federation();
+        let keys: Vec<H256> = members
+            .iter()
+            .map(|member| H256(member.public.to_bytes()))
+            .collect();
+        let aggregate = musig::KeyAggregate::new(&keys).unwrap();
+        let outpoint = OutPoint::new(H256::random(), 0);
+        let utxo = TransactionOutput::new(100, aggregate.public_key());
+        UtxoStore::<Test>::insert(outpoint, Some(utxo));
+        let mut tx = Transaction {
+            inputs: vec![TransactionInput::new(outpoint, vec![])],
+            outputs: vec![TransactionOutput::new(100, H256::from(alice_pub_key))],
+        };
+        let message = sig_message(&tx, 0);
+
+        // round 1: each member keeps its secret nonce and sends the public one as bytes
+        let secret_nonces: Vec<_> = members
+            .iter()
+            .map(|member| musig::commit(member, &message))
+            .collect();
+        let sent: Vec<[u8; 64]> = secret_nonces
+            .iter()
+            .map(|nonce| nonce.public().to_bytes())
+            .collect();
+        let nonces: Vec<_> = sent
+            .iter()
+            .rev()
+            .map(|bytes| musig::PublicNonce::from_bytes(bytes).unwrap())
+            .collect();
+
+        // round 2: each member signs on its own, and the shares are put together elsewhere
+        let partials: Vec<_> = members
+            .iter()
+            .zip(secret_nonces)
+            .map(|(member, nonce)| {
+                let partial =
+                    musig::sign_partial(member, nonce, &aggregate, &nonces, &message).unwrap();
+                musig::PartialSignature::from_bytes(&partial.to_bytes()).unwrap()
+            })
+            .collect();
+        for ((key, bytes), partial) in keys.iter().zip(&sent).zip(&partials) {
+            let nonce = musig::PublicNonce::from_bytes(bytes).unwrap();
+            assert!(musig::verify_partial(
+                &aggregate, &nonces, key, &nonce, partial, &message
+            ));
+        }
+
+        // a member sending another's share is found out, and spoils the signature
+        let nonce = musig::PublicNonce::from_bytes(&sent[0]).unwrap();
+        assert!(!musig::verify_partial(
+            &aggregate,
+            &nonces,
+            &keys[0],
+            &nonce,
+            &partials[1],
+            &message
+        ));
+        let bad_partials = vec![partials[1], partials[1], partials[2]];
+        assert_eq!(
+            musig::aggregate_signature(&aggregate, &nonces, &bad_partials, &message),
+            None
+        );
+
+        let signature =
+            musig::aggregate_signature(&aggregate, &nonces, &partials, &message).unwrap();
+        tx.inputs[0].witness = vec![signature.as_bytes().to_vec()];
+        assert_ok!(Utxo::spend(Origin::none(), tx));
+    })
+}
+
+#[test]
+fn attack_with_partial_musig_signature() {
+    execute_with_alice(|alice_pub_key| {
+        let members = federation();
+        let keys: Vec<H256> = members
+            .iter()
+            .map(|member| H256(member.public.to_bytes()))
+            .collect();
+        let outpoint = OutPoint::new(H256::random(), 0);
+        let utxo = TransactionOutput::new(100, musig::aggregate_key(&keys).unwrap());
+        UtxoStore::<Test>::insert(outpoint, Some(utxo));
+        let mut tx = Transaction {
+            inputs: vec![TransactionInput::new(outpoint, vec![])],
+            outputs: vec![TransactionOutput::new(100, H256::from(alice_pub_key))],
+        };
+
+        // two members sign for their own aggregate, not the federation's
+        let signature = musig::sign(&members[..2], &sig_message(&tx, 0)).unwrap();
+        tx.inputs[0].witness = vec![signature.as_bytes().to_vec()];
+        assert_noop!(
+            Utxo::spend(Origin::none(), tx),
+            Error::<Test>::SignatureFailure
+        );
+    })
+}
+
+// Three BLS utxos of their own keys, spent together to alice.
+fn execute_with_bls<F>(mut execute: F)
+where
+    F: FnMut(Transaction, Vec<[u8; BLS_PUBLIC_KEY_SIZE]>),
+{
+    execute_with_alice(|alice_pub_key| {
+        let keys: Vec<_> = (1..=3).map(test_bls_key).collect();
+        let inputs = keys
+            .iter()
+            .map(|key| {
+                let outpoint = OutPoint::new(H256::random(), 0);
+                UtxoStore::<Test>::insert(outpoint, Some(TransactionOutput::new_bls(100, key)));
+                TransactionInput::new(outpoint, vec![])
+            })
+            .collect();
+
+        let tx = Transaction {
+            inputs,
+            outputs: vec![TransactionOutput::new(300, H256::from(alice_pub_key))],
+        };
+        execute(tx, keys);
+    })
+}
+
+// The witness item of a BLS signature: the key, then its signature of the input.
+fn bls_item(key: &[u8], signature: &[u8]) -> Vec<u8> {
+    [key, signature].concat()
+}
+
+#[test]
+fn test_bls_signatures() {
+    execute_with_bls(|mut tx, keys| {
+        for (index, key) in keys.iter().enumerate() {
+            let signature = test_bls_sign(key, &sig_message(&tx, index));
+            tx.inputs[index].witness = vec![bls_item(key, &signature)];
+        }
+        assert_ok!(Utxo::spend(Origin::none(), tx));
+    })
+}
+
+#[test]
+fn test_bls_aggregate_signature() {
+    execute_with_bls(|mut tx, keys| {
+        let signatures: Vec<_> = keys
+            .iter()
+            .enumerate()
+            .map(|(index, key)| test_bls_sign(key, &sig_message(&tx, index)))
+            .collect();
+
+        // any one input carries the aggregate, the others only their keys
+        tx.inputs[0].witness = vec![keys[0].to_vec()];
+        tx.inputs[1].witness = vec![bls_item(&keys[1], &test_bls_aggregate(&signatures))];
+        tx.inputs[2].witness = vec![keys[2].to_vec()];
+        let outpoints: Vec<_> = tx.inputs.iter().map(|input| input.outpoint).collect();
+        assert_ok!(Utxo::spend(Origin::none(), tx));
+        assert!(outpoints
+            .iter()
+            .all(|outpoint| !UtxoStore::<Test>::contains_key(outpoint)));
+    })
+}
+
+#[test]
+fn test_bls_signatures_beside_key_sized_witness() {
+    execute_with_bls(|mut tx, keys| {
+        // a hash lock opened by a preimage as long as a BLS key
+        let preimage = [7u8; BLS_PUBLIC_KEY_SIZE];
+        let utxo = TransactionOutput::new_script(
+            100,
+            vec![
+                Op::Sha256,
+                Op::Push(sha2_256(&preimage).to_vec()),
+                Op::Equal,
+            ],
+        );
+        let outpoint = OutPoint::new(H256::random(), 0);
+        UtxoStore::<Test>::insert(outpoint, Some(utxo));
+        tx.inputs
+            .push(TransactionInput::new(outpoint, vec![preimage.to_vec()]));
+
+        // the BLS inputs sign on their own, which the preimage does not turn into an aggregate
+        for (index, key) in keys.iter().enumerate() {
+            let signature = test_bls_sign(key, &sig_message(&tx, index));
+            tx.inputs[index].witness = vec![bls_item(key, &signature)];
+        }
+        assert_ok!(Utxo::spend(Origin::none(), tx));
+    })
+}
+
+#[test]
+fn attack_with_partial_bls_aggregate() {
+    execute_with_bls(|mut tx, keys| {
+        // the aggregate lacks the signature of the last input
+        let signatures: Vec<_> = (0..2)
+            .map(|index| test_bls_sign(&keys[index], &sig_message(&tx, index)))
+            .collect();
+        tx.inputs[0].witness = vec![bls_item(&keys[0], &test_bls_aggregate(&signatures))];
+        tx.inputs[1].witness = vec![keys[1].to_vec()];
+        tx.inputs[2].witness = vec![keys[2].to_vec()];
+        assert_noop!(
+            Utxo::spend(Origin::none(), tx.clone()),
+            Error::<Test>::SignatureFailure
+        );
+
+        // an input signing on its own takes the place of the aggregate
+        let signature = test_bls_sign(&keys[2], &sig_message(&tx, 2));
+        tx.inputs[2].witness = vec![bls_item(&keys[2], &signature)];
+        assert_noop!(
+            Utxo::spend(Origin::none(), tx.clone()),
+            Error::<Test>::InvalidWitness
+        );
+
+        // without the aggregate, the keys alone sign nothing
+        tx.inputs[0].witness = vec![keys[0].to_vec()];
+        tx.inputs[2].witness = vec![keys[2].to_vec()];
+        assert_noop!(
+            Utxo::spend(Origin::none(), tx),
+            Error::<Test>::InvalidWitness
+        );
+    })
+}
+
+#[test]
+fn attack_with_wrong_bls_key() {
+    execute_with_bls(|mut tx, keys| {
+        let karl_key = test_bls_key(4);
+        for (index, key) in keys.iter().enumerate() {
+            let signature = test_bls_sign(key, &sig_message(&tx, index));
+            tx.inputs[index].witness = vec![bls_item(key, &signature)];
+        }
+        let karl_signature = test_bls_sign(&karl_key, &sig_message(&tx, 0));
+        tx.inputs[0].witness = vec![bls_item(&karl_key, &karl_signature)];
+        assert_noop!(
+            Utxo::spend(Origin::none(), tx.clone()),
+            Error::<Test>::SignatureFailure
+        );
+
+        // karl's key does not join an aggregate either
+        let signatures: Vec<_> = [karl_key, keys[1], keys[2]]
+            .iter()
+            .enumerate()
+            .map(|(index, key)| test_bls_sign(key, &sig_message(&tx, index)))
+            .collect();
+        tx.inputs[0].witness = vec![karl_key.to_vec()];
+        tx.inputs[1].witness = vec![bls_item(&keys[1], &test_bls_aggregate(&signatures))];
+        tx.inputs[2].witness = vec![keys[2].to_vec()];
+        assert_noop!(
+            Utxo::spend(Origin::none(), tx),
+            Error::<Test>::SignatureFailure
+        );
+    })
+}
+
+#[test]
+fn test_bls_needs_a_bls_verifier() {
+    execute_with_bls(|tx, keys| {
+        let utxo = TransactionOutput::new_bls(100, &keys[0]);
+        let message = sig_message(&tx, 0);
+        let item = bls_item(&keys[0], &test_bls_sign(&keys[0], &message));
+
+        assert_eq!(
+            DefaultVerifier::<TestBls>::verify(&utxo, &bls_key_hash(&keys[0]), &item, &message),
+            Ok(())
+        );
+        assert_eq!(
+            DefaultVerifier::<()>::verify(&utxo, &bls_key_hash(&keys[0]), &item, &message),
+            Err(SignatureError::UnsupportedMethod)
+        );
+        assert!(DefaultVerifier::<TestBls>::supports_bls());
+        assert!(!DefaultVerifier::<()>::supports_bls());
+        assert!(<DefaultVerifier>::supports_bls());
+    })
+}
+
+#[test]
+fn test_arkworks_bls() {
+    execute_with_bls(|tx, _| {
+        let key = ark_bls_key(11);
+        let message = sig_message(&tx, 0);
+        let signature = ark_bls_sign(&[(11, message.as_slice())]);
+        assert!(ArkworksBls::verify(&key, &signature, &message));
+        assert!(!ArkworksBls::verify(&key, &signature, &sig_message(&tx, 1)));
+        assert!(!ArkworksBls::verify(&ark_bls_key(12), &signature, &message));
+
+        // the default verifier spends a utxo of the key with it
+        let mut compressed_key = [0u8; BLS_PUBLIC_KEY_SIZE];
+        compressed_key.copy_from_slice(&key);
+        let utxo = TransactionOutput::new_bls(100, &compressed_key);
+        assert_eq!(
+            <DefaultVerifier>::verify(
+                &utxo,
+                &bls_key_hash(&key),
+                &bls_item(&key, &signature),
+                &message
+            ),
+            Ok(())
+        );
+
+        // an aggregate holds the signature of each message by its key
+        let other_key = ark_bls_key(12);
+        let other_message = sig_message(&tx, 1);
+        let keys = [key.as_slice(), other_key.as_slice()];
+        let messages = [message.as_slice(), other_message.as_slice()];
+        let aggregate = ark_bls_sign(&[(11, messages[0]), (12, messages[1])]);
+        assert!(ArkworksBls::aggregate_verify(&keys, &messages, &aggregate));
+        assert!(!ArkworksBls::aggregate_verify(
+            &keys[..1],
+            &messages[..1],
+            &aggregate
+        ));
+        let swapped = [messages[1], messages[0]];
+        assert!(!ArkworksBls::aggregate_verify(&keys, &swapped, &aggregate));
+
+        // the identity is no key
+        let identity = serialize(&G1Affine::zero());
+        assert!(!ArkworksBls::verify(
+            &identity,
+            &ark_bls_sign(&[]),
+            &message
+        ));
+    })
+}
+
+#[test]
+fn attack_with_bls_without_bls_verifier() {
+    execute_with_bls(|mut tx, keys| {
+        BLS_SUPPORTED.with(|supported| *supported.borrow_mut() = false);
+
+        // BLS inputs are refused cleanly
+        for index in 0..keys.len() {
+            let signature = test_bls_sign(&keys[index], &sig_message(&tx, index));
+            tx.inputs[index].witness = vec![bls_item(&keys[index], &signature)];
+        }
+        assert_noop!(
+            Utxo::spend(Origin::none(), tx.clone()),
+            Error::<Test>::UnsupportedSignatureMethod
+        );
+
+        // and so are new BLS outputs, which nobody could spend
+        let alice_pub_key = crypto::sr25519_public_keys(SR25519)[0];
+        let mut tx = Transaction {
+            inputs: vec![tx_input_gen_no_signature()],
+            outputs: vec![TransactionOutput::new_bls(100, &keys[0])],
+        };
+        let alice_sig =
+            crypto::sr25519_sign(SR25519, &alice_pub_key, &sig_message(&tx, 0)).unwrap();
+        tx.inputs[0].witness = vec![alice_sig.0.to_vec()];
+        assert_noop!(
+            Utxo::spend(Origin::none(), tx),
+            Error::<Test>::UnsupportedSignatureMethod
+        );
+    })
+}
+
+#[test]
+fn attack_with_bls_multisig() {
+    execute_with_bls(|_, keys| {
+        // each key of a multisig or script would take pairings the weight does not charge for
+        let alice_pub_key = crypto::sr25519_public_keys(SR25519)[0];
+        let bls_output = TransactionOutput::new_bls(100, &keys[0]);
+        let destinations = vec![
+            Destination::Multisig {
+                threshold: 2,
+                keys: keys.iter().map(|key| bls_key_hash(key)).collect(),
+            },
+            Destination::Script(vec![Op::key(bls_key_hash(&keys[0])), Op::CheckSig]),
+        ];
+        for destination in destinations {
+            let mut tx = Transaction {
+                inputs: vec![tx_input_gen_no_signature()],
+                outputs: vec![TransactionOutput {
+                    destination,
+                    ..bls_output.clone()
+                }],
+            };
+            let alice_sig =
+                crypto::sr25519_sign(SR25519, &alice_pub_key, &sig_message(&tx, 0)).unwrap();
+            tx.inputs[0].witness = vec![alice_sig.0.to_vec()];
+            assert_noop!(
+                Utxo::spend(Origin::none(), tx),
+                Error::<Test>::InvalidDestination
+            );
+        }
+    })
+}
+
+// A utxo locked to the square root circuit for the square of 7, spent back to alice.
+fn execute_with_zk<F>(mut execute: F)
+where
+    F: FnMut(Transaction, ProvingKey<Bls12_381>),
+{
+    execute_with_alice(|alice_pub_key| {
+        let (proving_key, verifying_key) = zk_setup(1);
+        let outpoint = OutPoint::new(H256::random(), 0);
+        UtxoStore::<Test>::insert(
+            outpoint,
+            Some(TransactionOutput::new_zk(
+                100,
+                &verifying_key,
+                &zk_square(7),
+            )),
+        );
+
+        let tx = Transaction {
+            inputs: vec![TransactionInput::new(outpoint, vec![])],
+            outputs: vec![TransactionOutput::new(100, H256::from(alice_pub_key))],
+        };
+        execute(tx, proving_key);
+    })
+}
+
+#[test]
+fn test_zk_proof() {
+    execute_with_zk(|mut tx, proving_key| {
+        let zk_proof = zk_prove(&proving_key, 7, &sig_message(&tx, 0));
+        tx.inputs[0].witness = vec![zk_proof.encode()];
+        assert_ok!(Utxo::spend(Origin::none(), tx));
+    })
+}
+
+#[test]
+fn attack_with_too_many_zk_inputs() {
+    execute_with_zk(|mut tx, proving_key| {
+        let verifying_key = zk_setup(1).1;
+        for index in 0..MAX_ZK_INPUTS {
+            let outpoint = OutPoint::new(H256::random(), index as u64);
+            UtxoStore::<Test>::insert(
+                outpoint,
+                Some(TransactionOutput::new_zk(
+                    100,
+                    &verifying_key,
+                    &zk_square(7),
+                )),
+            );
+            tx.inputs.push(TransactionInput::new(outpoint, vec![]));
+        }
+        for index in 0..tx.inputs.len() {
+            let zk_proof = zk_prove(&proving_key, 7, &sig_message(&tx, index));
+            tx.inputs[index].witness = vec![zk_proof.encode()];
+        }
+
+        // every proof is charged for, and there are too many to check them at all
+        assert_eq!(tx.zk_proofs(), MAX_ZK_INPUTS + 1);
+        assert_noop!(
+            Utxo::spend(Origin::none(), tx),
+            Error::<Test>::TooManyZkInputs
+        );
+    })
+}
+
+#[test]
+fn attack_with_zk_multisig() {
+    execute_with_alice(|alice_pub_key| {
+        // a multisig would take a proof per key to spend
+        let (_, verifying_key) = zk_setup(1);
+        let output = TransactionOutput {
+            destination: Destination::Multisig {
+                threshold: 2,
+                keys: vec![
+                    crate::zk::statement_hash(&verifying_key, &zk_square(7)),
+                    H256::random(),
+                ],
+            },
+            ..TransactionOutput::new_zk(50, &verifying_key, &zk_square(7))
+        };
+        let mut tx = Transaction {
+            inputs: vec![tx_input_gen_no_signature()],
+            outputs: vec![output],
+        };
+        let alice_sig =
+            crypto::sr25519_sign(SR25519, &alice_pub_key, &sig_message(&tx, 0)).unwrap();
+        tx.inputs[0].witness = vec![alice_sig.0.to_vec()];
+        assert_noop!(
+            Utxo::spend(Origin::none(), tx),
+            Error::<Test>::InvalidDestination
+        );
+    })
+}
+
+#[test]
+fn attack_with_replayed_zk_proof() {
+    execute_with_zk(|mut tx, proving_key| {
+        // the proof was made for a transaction paying karl
+        let mut karl_tx = tx.clone();
+        karl_tx.outputs[0] = TransactionOutput::new(100, H256::random());
+        let zk_proof = zk_prove(&proving_key, 7, &sig_message(&karl_tx, 0));
+
+        tx.inputs[0].witness = vec![zk_proof.encode()];
+        assert_noop!(
+            Utxo::spend(Origin::none(), tx),
+            Error::<Test>::SignatureFailure
+        );
+    })
+}
+
+#[test]
+fn attack_with_other_zk_statement() {
+    execute_with_zk(|mut tx, proving_key| {
+        let message = sig_message(&tx, 0);
+
+        // a valid proof of the circuit, but for the square of 8, which the utxo is not locked to
+        tx.inputs[0].witness = vec![zk_prove(&proving_key, 8, &message).encode()];
+        assert_noop!(
+            Utxo::spend(Origin::none(), tx.clone()),
+            Error::<Test>::SignatureFailure
+        );
+
+        // the proof is for the square of 7, not of 8
+        let mut zk_proof = zk_prove(&proving_key, 7, &message);
+        zk_proof.public_inputs = zk_square(8);
+        tx.inputs[0].witness = vec![zk_proof.encode()];
+        assert_noop!(
+            Utxo::spend(Origin::none(), tx.clone()),
+            Error::<Test>::SignatureFailure
+        );
+
+        // a valid proof under a verifying key the utxo is not locked to
+        let (other_proving_key, _) = zk_setup(2);
+        tx.inputs[0].witness = vec![zk_prove(&other_proving_key, 7, &message).encode()];
+        assert_noop!(
+            Utxo::spend(Origin::none(), tx.clone()),
+            Error::<Test>::SignatureFailure
+        );
+
+        tx.inputs[0].witness = vec![vec![0u8; 64]];
+        assert_noop!(
+            Utxo::spend(Origin::none(), tx),
+            Error::<Test>::SignatureFailure
+        );
+    })
+}
+
+#[test]
+fn test_issue_token() {
+    execute_with_alice(|alice_pub_key| {
+        let token_id = FIRST_ISSUED_TOKEN_ID;
+        assert_ok!(Utxo::issue_token(
+            Origin::signed(1),
+            b"TKN".to_vec(),
+            8,
+            H256::from(alice_pub_key),
+            1_000,
+            None
+        ));
+        let info = TokenRegistry::<Test>::get(token_id).unwrap();
+        assert_eq!(info.ticker(), b"TKN");
+        assert_eq!(info.decimals(), 8);
+        assert_eq!(info.issuer(), H256::from(alice_pub_key));
+        assert_eq!(info.initial_supply(), 1_000);
+        assert_eq!(TokenSupply::<Test>::get(token_id), 1_000);
+        assert_eq!(NextTokenId::<Test>::get(), token_id + 1);
+
+        let supply =
+            TransactionOutput::new(1_000, H256::from(alice_pub_key)).with_token_id(token_id);
+        let outpoint = Transaction {
+            inputs: vec![],
+            outputs: vec![supply.clone()],
+        }
+        .outpoint(0);
+        assert_eq!(UtxoStore::<Test>::get(outpoint), Some(supply));
+
+        // alice sends the whole supply to karl, and pays the fee in MLT
+        let karl_pub_key = H256::random();
+        let mut tx = Transaction {
+            inputs: vec![
+                tx_input_gen_no_signature(),
+                TransactionInput::new(outpoint, vec![]),
+            ],
+            outputs: vec![
+                TransactionOutput::new(90, karl_pub_key),
+                TransactionOutput::new(1_000, karl_pub_key).with_token_id(token_id),
+            ],
+        };
+        for index in 0..2 {
+            let alice_sig =
+                crypto::sr25519_sign(SR25519, &alice_pub_key, &sig_message(&tx, index)).unwrap();
+            tx.inputs[index].witness = vec![alice_sig.0.to_vec()];
+        }
+        assert_ok!(Utxo::spend(Origin::none(), tx));
+    })
+}
+
+#[test]
+fn attack_with_unregistered_token() {
+    execute_with_alice(|alice_pub_key| {
+        let mut tx = Transaction {
+            inputs: vec![tx_input_gen_no_signature()],
+            outputs: vec![TransactionOutput::new(50, H256::from(alice_pub_key))
+                .with_token_id(FIRST_ISSUED_TOKEN_ID)],
+        };
+        let alice_sig =
+            crypto::sr25519_sign(SR25519, &alice_pub_key, &sig_message(&tx, 0)).unwrap();
+        tx.inputs[0].witness = vec![alice_sig.0.to_vec()];
+
+        assert_noop!(
+            Utxo::spend(Origin::none(), tx),
+            Error::<Test>::InvalidHeader
+        );
+    })
+}
+
+#[test]
+fn attack_with_invalid_token_issuance() {
+    execute_with_alice(|alice_pub_key| {
+        let issue = |origin, ticker: &[u8], decimals, supply| {
+            Utxo::issue_token(
+                origin,
+                ticker.to_vec(),
+                decimals,
+                H256::from(alice_pub_key),
+                supply,
+                None,
+            )
+        };
+
+        assert_noop!(
+            issue(Origin::none(), b"TKN", 8, 1_000),
+            DispatchError::BadOrigin
+        );
+        for ticker in [&b""[..], b"TOOLONG", b"tkn", b"T-N"].iter() {
+            assert_noop!(
+                issue(Origin::signed(1), ticker, 8, 1_000),
+                Error::<Test>::InvalidTicker
+            );
+        }
+        assert_noop!(
+            issue(Origin::signed(1), b"TKN", 19, 1_000),
+            Error::<Test>::TooManyDecimals
+        );
+        assert_noop!(
+            issue(Origin::signed(1), b"TKN", 8, 0),
+            Error::<Test>::ZeroSupply
+        );
+        assert_noop!(
+            Utxo::issue_token(
+                Origin::signed(1),
+                b"TKN".to_vec(),
+                8,
+                H256::from(alice_pub_key),
+                1_000,
+                Some(999)
+            ),
+            Error::<Test>::SupplyCapExceeded
+        );
+        assert_eq!(NextTokenId::<Test>::get(), FIRST_ISSUED_TOKEN_ID);
+    })
+}
+
+// Alice issues 1000 of a token capped at 1500, and holds its supply along with her MLT.
+fn execute_with_token<F>(mut execute: F)
+where
+    F: FnMut(Public, TokenID, OutPoint),
+{
+    execute_with_alice(|alice_pub_key| {
+        let token_id = FIRST_ISSUED_TOKEN_ID;
+        assert_ok!(Utxo::issue_token(
+            Origin::signed(1),
+            b"TKN".to_vec(),
+            8,
+            H256::from(alice_pub_key),
+            1_000,
+            Some(1_500)
+        ));
+        let outpoint = Transaction {
+            inputs: vec![],
+            outputs: vec![
+                TransactionOutput::new(1_000, H256::from(alice_pub_key)).with_token_id(token_id)
+            ],
+        }
+        .outpoint(0);
+        execute(alice_pub_key, token_id, outpoint);
+    })
+}
+
+fn mint_signature(issuer: &Public, token_id: TokenID, amount: Value, recipient: &H256) -> H512 {
+    let mints = TokenRegistry::<Test>::get(token_id).map_or(0, |info| info.mints());
+    let message = mint_message(token_id, mints, amount, recipient);
+    H512(crypto::sr25519_sign(SR25519, issuer, &message).unwrap().0)
+}
+
+#[test]
+fn test_mint() {
+    execute_with_token(|alice_pub_key, token_id, _| {
+        let karl_pub_key = H256::random();
+        let signature = mint_signature(&alice_pub_key, token_id, 500, &karl_pub_key);
+        assert_ok!(Utxo::mint(
+            Origin::signed(1),
+            token_id,
+            500,
+            karl_pub_key,
+            signature
+        ));
+        assert_eq!(TokenSupply::<Test>::get(token_id), 1_500);
+        assert_eq!(TokenRegistry::<Test>::get(token_id).unwrap().mints(), 1);
+
+        let minted = Transaction {
+            inputs: vec![TransactionInput::new(
+                OutPoint::new(BlakeTwo256::hash(b"mint"), 0),
+                vec![],
+            )],
+            outputs: vec![TransactionOutput::new(500, karl_pub_key).with_token_id(token_id)],
+        };
+        assert!(UtxoStore::<Test>::get(minted.outpoint(0)).is_some());
+
+        // the signature committed to the first mint
+        assert_noop!(
+            Utxo::mint(Origin::signed(1), token_id, 500, karl_pub_key, signature),
+            Error::<Test>::SignatureFailure
+        );
+
+        // the cap is reached
+        let signature = mint_signature(&alice_pub_key, token_id, 1, &karl_pub_key);
+        assert_noop!(
+            Utxo::mint(Origin::signed(1), token_id, 1, karl_pub_key, signature),
+            Error::<Test>::SupplyCapExceeded
+        );
+    })
+}
+
+#[test]
+fn attack_with_mint_over_coinbase() {
+    execute_with_token(|alice_pub_key, token_id, _| {
+        let recipient = H256::from(alice_pub_key);
+
+        // the reward of block 0, in the token, to alice as the only authority
+        RewardTotal::<Test>::insert(token_id, 250);
+        Utxo::on_finalize(0);
+        let coinbase = Transaction {
+            inputs: vec![TransactionInput::new(
+                OutPoint::new(H256::zero(), 0),
+                vec![],
+            )],
+            outputs: vec![TransactionOutput::new(250, recipient).with_token_id(token_id)],
+        };
+        assert!(UtxoStore::<Test>::contains_key(coinbase.outpoint(0)));
+
+        // the first mint of the same amount to alice lands elsewhere
+        let signature = mint_signature(&alice_pub_key, token_id, 250, &recipient);
+        assert_ok!(Utxo::mint(
+            Origin::signed(1),
+            token_id,
+            250,
+            recipient,
+            signature
+        ));
+        let utxo = TransactionOutput::new(250, recipient).with_token_id(token_id);
+        let minted = mint_outpoint(&utxo, 0);
+        assert_ne!(minted, coinbase.outpoint(0));
+        assert!(UtxoStore::<Test>::contains_key(minted));
+        assert!(UtxoStore::<Test>::contains_key(coinbase.outpoint(0)));
+
+        // an existing utxo is never overwritten
+        UtxoStore::<Test>::insert(mint_outpoint(&utxo, 1), Some(utxo.clone()));
+        let signature = mint_signature(&alice_pub_key, token_id, 250, &recipient);
+        assert_noop!(
+            Utxo::mint(Origin::signed(1), token_id, 250, recipient, signature),
+            Error::<Test>::OutputAlreadyExists
+        );
+    })
+}
+
+#[test]
+fn attack_with_mint_by_non_issuer() {
+    execute_with_token(|_, token_id, _| {
+        let karl_pub_key = crypto::sr25519_generate(SR25519, None);
+        let recipient = H256::from(karl_pub_key);
+        let signature = mint_signature(&karl_pub_key, token_id, 500, &recipient);
+        assert_noop!(
+            Utxo::mint(Origin::signed(1), token_id, 500, recipient, signature),
+            Error::<Test>::SignatureFailure
+        );
+
+        let signature = mint_signature(&karl_pub_key, token_id + 1, 500, &recipient);
+        assert_noop!(
+            Utxo::mint(Origin::signed(1), token_id + 1, 500, recipient, signature),
+            Error::<Test>::TokenNotFound
+        );
+    })
+}
+
+// Alice spends her MLT and her token supply, to `token_outputs` and to the 90 MLT left
+// after the fee.
+fn token_tx(
+    alice_pub_key: Public,
+    outpoint: OutPoint,
+    token_outputs: Vec<TransactionOutput>,
+) -> Transaction {
+    let mut outputs = vec![TransactionOutput::new(90, H256::from(alice_pub_key))];
+    outputs.extend(token_outputs);
+    let mut tx = Transaction {
+        inputs: vec![
+            tx_input_gen_no_signature(),
+            TransactionInput::new(outpoint, vec![]),
+        ],
+        outputs,
+    };
+    for index in 0..2 {
+        let alice_sig =
+            crypto::sr25519_sign(SR25519, &alice_pub_key, &sig_message(&tx, index)).unwrap();
+        tx.inputs[index].witness = vec![alice_sig.0.to_vec()];
+    }
+    tx
+}
+
+#[test]
+fn test_burn() {
+    execute_with_token(|alice_pub_key, token_id, outpoint| {
+        let karl_pub_key = H256::random();
+        let tx = token_tx(
+            alice_pub_key,
+            outpoint,
+            vec![
+                TransactionOutput::new(600, karl_pub_key).with_token_id(token_id),
+                TransactionOutput::new_burn(400).with_token_id(token_id),
+            ],
+        );
+        let burnt = tx.outpoint(2);
+        assert_ok!(Utxo::spend(Origin::none(), tx));
+
+        assert_eq!(TokenSupply::<Test>::get(token_id), 600);
+        assert!(!UtxoStore::<Test>::contains_key(burnt));
+    })
+}
+
+#[test]
+fn test_burn_only() {
+    execute_with_token(|alice_pub_key, token_id, outpoint| {
+        // no change output: all of the token is burnt
+        let mut tx = Transaction {
+            inputs: vec![TransactionInput::new(outpoint, vec![])],
+            outputs: vec![TransactionOutput::new_burn(1_000).with_token_id(token_id)],
+        };
+        let alice_sig =
+            crypto::sr25519_sign(SR25519, &alice_pub_key, &sig_message(&tx, 0)).unwrap();
+        tx.inputs[0].witness = vec![alice_sig.0.to_vec()];
+
+        // the pool refuses transactions providing no tag
+        let valid_tx = crate::validate_transaction::<Test>(&tx).unwrap();
+        assert_eq!(valid_tx.provides, vec![(b"spent", outpoint).encode()]);
+        assert_ok!(Utxo::spend(Origin::none(), tx));
+        assert_eq!(TokenSupply::<Test>::get(token_id), 0);
+    })
+}
+
+#[test]
+fn test_token_fee() {
+    execute_with_token(|alice_pub_key, token_id, outpoint| {
+        // the 400 of the token not sent on are a fee, along with the 10 MLT
+        let tx = token_tx(
+            alice_pub_key,
+            outpoint,
+            vec![TransactionOutput::new(600, H256::random()).with_token_id(token_id)],
+        );
+        let valid_tx = crate::validate_transaction::<Test>(&tx).unwrap();
+        assert_eq!(valid_tx.priority, 10);
+        assert_ok!(Utxo::spend(Origin::none(), tx));
+
+        assert_eq!(RewardTotal::<Test>::get(TokenType::MLT as TokenID), 10);
+        assert_eq!(RewardTotal::<Test>::get(token_id), 400);
+        // fees stay in circulation
+        assert_eq!(TokenSupply::<Test>::get(token_id), 1_000);
+    })
+}
+
+#[test]
+fn test_token_only_transfer() {
+    execute_with_token(|alice_pub_key, token_id, outpoint| {
+        let karl_pub_key = H256::random();
+        let mut tx = Transaction {
+            inputs: vec![TransactionInput::new(outpoint, vec![])],
+            outputs: vec![TransactionOutput::new(990, karl_pub_key).with_token_id(token_id)],
+        };
+        let alice_sig =
+            crypto::sr25519_sign(SR25519, &alice_pub_key, &sig_message(&tx, 0)).unwrap();
+        tx.inputs[0].witness = vec![alice_sig.0.to_vec()];
+
+        // no MLT is paid, so the transaction has no priority. It only passes because the mock's
+        // minimum fee is zero, see `test_token_only_transfer_with_minimum_fee`
+        let valid_tx = crate::validate_transaction::<Test>(&tx).unwrap();
+        assert_eq!(valid_tx.priority, 0);
+        assert_ok!(Utxo::spend(Origin::none(), tx.clone()));
+
+        assert_eq!(RewardTotal::<Test>::get(TokenType::MLT as TokenID), 0);
+        assert_eq!(RewardTotal::<Test>::get(token_id), 10);
+        assert_eq!(UtxoStore::<Test>::get(tx.outpoint(0)).unwrap().value, 990);
+    })
+}
+
+#[test]
+fn test_token_only_transfer_with_minimum_fee() {
+    execute_with_token(|alice_pub_key, token_id, outpoint| {
+        FEE_PER_INPUT.with(|fee| *fee.borrow_mut() = 10);
+        FEE_PER_OUTPUT.with(|fee| *fee.borrow_mut() = 10);
+
+        // the minimum fee is paid in MLT only, however much of the token is left over
+        let mut tx = Transaction {
+            inputs: vec![TransactionInput::new(outpoint, vec![])],
+            outputs: vec![TransactionOutput::new(1, H256::random()).with_token_id(token_id)],
+        };
+        let alice_sig =
+            crypto::sr25519_sign(SR25519, &alice_pub_key, &sig_message(&tx, 0)).unwrap();
+        tx.inputs[0].witness = vec![alice_sig.0.to_vec()];
+        assert_noop!(
+            Utxo::spend(Origin::none(), tx),
+            Error::<Test>::InsufficientFee
+        );
+
+        // the same transfer goes through once an MLT input pays the fee
+        let mut tx = Transaction {
+            inputs: vec![
+                tx_input_gen_no_signature(),
+                TransactionInput::new(outpoint, vec![]),
+            ],
+            outputs: vec![
+                TransactionOutput::new(60, H256::from(alice_pub_key)),
+                TransactionOutput::new(1_000, H256::random()).with_token_id(token_id),
+            ],
+        };
+        for index in 0..2 {
+            let alice_sig =
+                crypto::sr25519_sign(SR25519, &alice_pub_key, &sig_message(&tx, index)).unwrap();
+            tx.inputs[index].witness = vec![alice_sig.0.to_vec()];
+        }
+        assert_eq!(minimum_fee::<Test>(&tx), 40);
+        assert_ok!(Utxo::spend(Origin::none(), tx.clone()));
+        assert_eq!(RewardTotal::<Test>::get(TokenType::MLT as TokenID), 40);
+        assert_eq!(UtxoStore::<Test>::get(tx.outpoint(1)).unwrap().value, 1_000);
+    })
+}
+
+#[test]
+fn test_token_reward_outpoints() {
+    execute_with_token(|alice_pub_key, token_id, _| {
+        RewardTotal::<Test>::insert(TokenType::MLT as TokenID, 10);
+        RewardTotal::<Test>::insert(token_id, 30);
+        Utxo::on_finalize(7);
+
+        // the coinbase of block 7 pays the rewards of each token, in the order of the token ids
+        let coinbase = Transaction {
+            inputs: vec![TransactionInput::new(
+                OutPoint::new(H256::zero(), 7),
+                vec![],
+            )],
+            outputs: vec![
+                TransactionOutput::new(10, H256::from(alice_pub_key)),
+                TransactionOutput::new(30, H256::from(alice_pub_key)).with_token_id(token_id),
+            ],
+        };
+        let reward = UtxoStore::<Test>::get(coinbase.outpoint(1)).unwrap();
+        assert_eq!(reward.value, 30);
+        assert_eq!(OutputHeader::new(reward.header).token_id(), token_id);
+        assert_eq!(RewardTotal::<Test>::get(TokenType::MLT as TokenID), 0);
+        assert_eq!(RewardTotal::<Test>::get(token_id), 0);
+    })
+}
+
+fn mint_nft_signature(issuer: &Public, token_id: TokenID, nft: &NftData, recipient: &H256) -> H512 {
+    let mints = TokenRegistry::<Test>::get(token_id).map_or(0, |info| info.mints());
+    let message = mint_nft_message(token_id, mints, nft, recipient);
+    H512(crypto::sr25519_sign(SR25519, issuer, &message).unwrap().0)
+}
+
+// Alice issues an NFT class and mints herself an NFT of it, which she holds along with her MLT.
+fn execute_with_nft<F>(mut execute: F)
+where
+    F: FnMut(Public, TokenID, NftData, OutPoint),
+{
+    execute_with_alice(|alice_pub_key| {
+        let token_id = FIRST_ISSUED_TOKEN_ID;
+        let issuer = H256::from(alice_pub_key);
+        assert_ok!(Utxo::issue_nft_class(
+            Origin::signed(1),
+            b"ART".to_vec(),
+            issuer
+        ));
+
+        let nft = NftData::new(H256::repeat_byte(7), Some(b"ipfs://art".to_vec()));
+        let signature = mint_nft_signature(&alice_pub_key, token_id, &nft, &issuer);
+        assert_ok!(Utxo::mint_nft(
+            Origin::signed(1),
+            token_id,
+            nft.clone(),
+            issuer,
+            signature
+        ));
+        assert_eq!(TokenSupply::<Test>::get(token_id), 1);
+
+        let outpoint = Nfts::<Test>::get(token_id, nft.content_hash()).unwrap();
+        assert_eq!(
+            UtxoStore::<Test>::get(outpoint),
+            Some(
+                TransactionOutput::new(1, issuer)
+                    .with_token_id(token_id)
+                    .with_nft(nft.clone())
+            )
+        );
+        execute(alice_pub_key, token_id, nft, outpoint);
+    })
+}
+
+#[test]
+fn test_nft_transfer() {
+    execute_with_nft(|alice_pub_key, token_id, nft, outpoint| {
+        let karl_pub_key = H256::random();
+        let tx = token_tx(
+            alice_pub_key,
+            outpoint,
+            vec![TransactionOutput::new(1, karl_pub_key)
+                .with_token_id(token_id)
+                .with_nft(nft.clone())],
+        );
+        let transferred = tx.outpoint(1);
+        assert_ok!(Utxo::spend(Origin::none(), tx));
+
+        assert_eq!(
+            Nfts::<Test>::get(token_id, nft.content_hash()),
+            Some(transferred)
+        );
+        assert_eq!(TokenSupply::<Test>::get(token_id), 1);
+    })
+}
+
+#[test]
+fn test_nft_burn() {
+    execute_with_nft(|alice_pub_key, token_id, nft, outpoint| {
+        let tx = token_tx(
+            alice_pub_key,
+            outpoint,
+            vec![TransactionOutput::new_burn(1)
+                .with_token_id(token_id)
+                .with_nft(nft.clone())],
+        );
+        assert_ok!(Utxo::spend(Origin::none(), tx));
+
+        assert_eq!(Nfts::<Test>::get(token_id, nft.content_hash()), None);
+        assert_eq!(TokenSupply::<Test>::get(token_id), 0);
+    })
+}
+
+#[test]
+fn attack_by_changing_nft() {
+    execute_with_nft(|alice_pub_key, token_id, nft, outpoint| {
+        let karl_pub_key = H256::random();
+        let to_karl = |value, nft: Option<NftData>| {
+            let output = TransactionOutput::new(value, karl_pub_key).with_token_id(token_id);
+            match nft {
+                Some(nft) => output.with_nft(nft),
+                None => output,
+            }
+        };
+
+        // the metadata is immutable
+        let forged = NftData::new(nft.content_hash(), Some(b"ipfs://forged".to_vec()));
+        let tx = token_tx(alice_pub_key, outpoint, vec![to_karl(1, Some(forged))]);
+        assert_noop!(
+            Utxo::spend(Origin::none(), tx),
+            Error::<Test>::NftNotTransferred
+        );
+
+        // an NFT is not split, nor does it lose its data
+        for output in vec![to_karl(2, Some(nft.clone())), to_karl(1, None)] {
+            let tx = token_tx(alice_pub_key, outpoint, vec![output]);
+            assert_noop!(Utxo::spend(Origin::none(), tx), Error::<Test>::InvalidNft);
+        }
+
+        // nor is it created by a transaction, along with the one spent
+        let copy = NftData::new(H256::repeat_byte(8), None);
+        let tx = token_tx(
+            alice_pub_key,
+            outpoint,
+            vec![to_karl(1, Some(nft.clone())), to_karl(1, Some(copy))],
+        );
+        assert_noop!(
+            Utxo::spend(Origin::none(), tx),
+            Error::<Test>::NftNotTransferred
+        );
+
+        // nor does it vanish
+        let tx = token_tx(alice_pub_key, outpoint, vec![]);
+        assert_noop!(
+            Utxo::spend(Origin::none(), tx),
+            Error::<Test>::NftNotTransferred
+        );
+    })
+}
+
+#[test]
+fn attack_by_minting_nft_twice() {
+    execute_with_nft(|alice_pub_key, token_id, nft, _| {
+        let issuer = H256::from(alice_pub_key);
+        let signature = mint_nft_signature(&alice_pub_key, token_id, &nft, &issuer);
+        assert_noop!(
+            Utxo::mint_nft(Origin::signed(1), token_id, nft.clone(), issuer, signature),
+            Error::<Test>::NftAlreadyExists
+        );
+
+        // NFT classes and fungible tokens are minted their own way
+        let signature = mint_signature(&alice_pub_key, token_id, 1, &issuer);
+        assert_noop!(
+            Utxo::mint(Origin::signed(1), token_id, 1, issuer, signature),
+            Error::<Test>::WrongTokenKind
+        );
+        assert_ok!(Utxo::issue_token(
+            Origin::signed(1),
+            b"TKN".to_vec(),
+            8,
+            issuer,
+            1_000,
+            None
+        ));
+        let signature = mint_nft_signature(&alice_pub_key, token_id + 1, &nft, &issuer);
+        assert_noop!(
+            Utxo::mint_nft(
+                Origin::signed(1),
+                token_id + 1,
+                nft.clone(),
+                issuer,
+                signature
+            ),
+            Error::<Test>::WrongTokenKind
+        );
+    })
+}
+
+// Alice spends a utxo of `value` MLT to herself, leaving `fee` to the block authors.
+fn fee_tx(alice_pub_key: Public, outpoint: OutPoint, value: Value, fee: Value) -> Transaction {
+    let mut tx = Transaction {
+        inputs: vec![TransactionInput::new(outpoint, vec![])],
+        outputs: vec![TransactionOutput::new(
+            value - fee,
+            H256::from(alice_pub_key),
+        )],
+    };
+    let alice_sig = crypto::sr25519_sign(SR25519, &alice_pub_key, &sig_message(&tx, 0)).unwrap();
+    tx.inputs[0].witness = vec![alice_sig.0.to_vec()];
+    tx
+}
+
+#[test]
+fn test_minimum_fee_per_input_and_output() {
+    execute_with_alice(|alice_pub_key| {
+        FEE_PER_INPUT.with(|fee| *fee.borrow_mut() = 3);
+        FEE_PER_OUTPUT.with(|fee| *fee.borrow_mut() = 2);
+        assert_eq!(
+            minimum_fee::<Test>(&fee_tx(alice_pub_key, genesis_outpoint(), 100, 0)),
+            5
+        );
+
+        // free transactions are refused
+        let tx = fee_tx(alice_pub_key, genesis_outpoint(), 100, 0);
+        assert_err!(
+            crate::validate_transaction::<Test>(&tx),
+            Error::<Test>::InsufficientFee
+        );
+
+        let tx = fee_tx(alice_pub_key, genesis_outpoint(), 100, 4);
+        assert_noop!(
+            Utxo::spend(Origin::none(), tx),
+            Error::<Test>::InsufficientFee
+        );
+
+        let tx = fee_tx(alice_pub_key, genesis_outpoint(), 100, 5);
+        assert_ok!(Utxo::spend(Origin::none(), tx));
+        assert_eq!(RewardTotal::<Test>::get(TokenType::MLT as TokenID), 5);
+    })
+}
+
+#[test]
+fn test_minimum_fee_per_byte() {
+    execute_with_alice(|alice_pub_key| {
+        let outpoint = OutPoint::new(H256::random(), 0);
+        UtxoStore::<Test>::insert(
+            outpoint,
+            Some(TransactionOutput::new(1_000, H256::from(alice_pub_key))),
+        );
+        FEE_PER_BYTE.with(|fee| *fee.borrow_mut() = 2);
+
+        // the fee does not change the size of the transaction
+        let size = fee_tx(alice_pub_key, outpoint, 1_000, 0).encode().len() as Value;
+        let tx = fee_tx(alice_pub_key, outpoint, 1_000, 2 * size);
+        assert_eq!(minimum_fee::<Test>(&tx), 2 * size);
+
+        let short_tx = fee_tx(alice_pub_key, outpoint, 1_000, 2 * size - 1);
+        assert_noop!(
+            Utxo::spend(Origin::none(), short_tx),
+            Error::<Test>::InsufficientFee
+        );
+        assert_ok!(Utxo::spend(Origin::none(), tx));
+    })
+}
+
+#[test]
+fn test_minimum_fee_in_mlt() {
+    execute_with_token(|alice_pub_key, token_id, outpoint| {
+        FEE_PER_INPUT.with(|fee| *fee.borrow_mut() = 10);
+
+        // paying in another token does not count
+        let mut tx = Transaction {
+            inputs: vec![TransactionInput::new(outpoint, vec![])],
+            outputs: vec![TransactionOutput::new(900, H256::random()).with_token_id(token_id)],
+        };
+        let alice_sig =
+            crypto::sr25519_sign(SR25519, &alice_pub_key, &sig_message(&tx, 0)).unwrap();
+        tx.inputs[0].witness = vec![alice_sig.0.to_vec()];
+        assert_noop!(
+            Utxo::spend(Origin::none(), tx),
+            Error::<Test>::InsufficientFee
+        );
+
+        // a fee of 10 MLT does not cover two inputs at 10 each, but it covers them at 5
+        let tx = token_tx(
+            alice_pub_key,
+            outpoint,
+            vec![TransactionOutput::new(1_000, H256::random()).with_token_id(token_id)],
+        );
+        assert_noop!(
+            Utxo::spend(Origin::none(), tx),
+            Error::<Test>::InsufficientFee
+        );
+        FEE_PER_INPUT.with(|fee| *fee.borrow_mut() = 5);
+        let tx = token_tx(
+            alice_pub_key,
+            outpoint,
+            vec![TransactionOutput::new(1_000, H256::random()).with_token_id(token_id)],
+        );
+        assert_ok!(Utxo::spend(Origin::none(), tx));
+    })
+}
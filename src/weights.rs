@@ -1,10 +1,11 @@
-//! Autogenerated weights for pallet_utxo
+//! Weights for pallet_utxo
 //!
-//! THIS FILE WAS AUTO-GENERATED USING THE SUBSTRATE BENCHMARK CLI VERSION 3.0.0
-//! DATE: 2021-06-21, STEPS: `[50, ]`, REPEAT: 20, LOW RANGE: `[]`, HIGH RANGE: `[]`
-//! EXECUTION: Some(Wasm), WASM-EXECUTION: Compiled, CHAIN: Some("dev"), DB CACHE: 128
+//! These are not the output of the benchmark CLI. The baseline of `spend` is copied from
+//! Substrate's, and every other figure is an estimate, marked as such along with the benchmark
+//! that is to replace it. Once the benchmarks are run on reference hardware, this file is to be
+//! regenerated from them.
 
-// Executed Command:
+// Command to regenerate it:
 // target/release/node-template
 // benchmark
 // --chain
@@ -14,7 +15,7 @@
 // --pallet
 // pallet_utxo
 // --extrinsic
-// spend
+// *
 // --steps
 // 50
 // --repeat
@@ -32,12 +33,84 @@ use sp_core::sp_std::marker::PhantomData;
 /// Weight functions for pallet_utxo.
 pub struct WeightInfo<T>(PhantomData<T>);
 impl<T: frame_system::Config> crate::WeightInfo for WeightInfo<T> {
-    fn spend(s: u32) -> Weight {
+    fn spend(s: u32, w: u32, z: u32, c: u32) -> Weight {
         (348_270_000 as Weight)
             // Standard Error: 2_000
             //TODO: literally just copying from substrate's
+            // Per input or output, as `spend_inputs` checks the signatures one at a time, the
+            // way the transaction pool does. `spend_inputs_batched` checks them in a batch, the
+            // way block import does, which spreads them over other threads rather than making
+            // them cheaper, so the same figure holds for it.
             .saturating_add((1_146_000 as Weight).saturating_mul(s as Weight))
+            // per byte of witness, from `spend_witness`: an estimate of decoding, hashing and
+            // storing each byte at 10ns, until the benchmark is run on reference hardware
+            .saturating_add((10_000 as Weight).saturating_mul(w as Weight))
+            // per Groth16 proof, from `spend_zk`: an estimate of preparing the key and checking
+            // the pairings in wasm at 50ms, until the benchmark is run on reference hardware.
+            // BLS signatures take fewer pairings, and are charged the same.
+            .saturating_add((50_000_000_000 as Weight).saturating_mul(z as Weight))
+            // per witness item, each of which may be a signature to check, from `spend_multisig`:
+            // an estimate of an sr25519 verification at 50us, until the benchmark is run on
+            // reference hardware
+            .saturating_add((50_000_000 as Weight).saturating_mul(c as Weight))
+            // the chain time, for the time locks
+            .saturating_add(T::DbWeight::get().reads(1 as Weight))
+            // Per input, its utxo, creation block and reward total are read and written. Per
+            // output, its outpoint, token and supply are read, and its utxo, creation block and
+            // NFT are written, or its NFT and the supply if it is burnt.
+            .saturating_add(T::DbWeight::get().reads((3 as Weight).saturating_mul(s as Weight)))
+            .saturating_add(T::DbWeight::get().writes((3 as Weight).saturating_mul(s as Weight)))
+    }
+
+    fn issue_token() -> Weight {
+        // an estimate of registering the token and storing its utxo, until `issue_token` is run
+        // on reference hardware
+        (45_000_000 as Weight)
+            .saturating_add(T::DbWeight::get().reads(1 as Weight))
+            .saturating_add(T::DbWeight::get().writes(5 as Weight))
+    }
+
+    fn mint() -> Weight {
+        // an estimate of checking the issuer's sr25519 signature and storing the utxo, until
+        // `mint` is run on reference hardware
+        (95_000_000 as Weight)
             .saturating_add(T::DbWeight::get().reads(3 as Weight))
+            .saturating_add(T::DbWeight::get().writes(4 as Weight))
+    }
+
+    fn issue_nft_class() -> Weight {
+        // an estimate of registering the class, until `issue_nft_class` is run on reference
+        // hardware
+        (40_000_000 as Weight)
+            .saturating_add(T::DbWeight::get().reads(1 as Weight))
             .saturating_add(T::DbWeight::get().writes(3 as Weight))
     }
+
+    fn mint_nft() -> Weight {
+        // an estimate of checking the issuer's sr25519 signature and storing the NFT, until
+        // `mint_nft` is run on reference hardware
+        (100_000_000 as Weight)
+            .saturating_add(T::DbWeight::get().reads(4 as Weight))
+            .saturating_add(T::DbWeight::get().writes(5 as Weight))
+    }
+
+    fn disperse_reward(t: u32, a: u32) -> Weight {
+        // an estimate of hashing the coinbase and storing its utxos, per token `t` and per
+        // share of a token among the `a` authorities, until `disperse_reward`, which varies
+        // both, is run on reference hardware
+        (10_000_000 as Weight)
+            .saturating_add((2_000_000 as Weight).saturating_mul(t as Weight))
+            .saturating_add(
+                (1_000_000 as Weight).saturating_mul((t as Weight).saturating_mul(a as Weight)),
+            )
+            // the authorities and the reward cursor, which is moved, then per token its reward
+            // total, which is removed, and per authority the outpoint of its share, which is
+            // stored with its creation block
+            .saturating_add(T::DbWeight::get().reads(2 as Weight))
+            .saturating_add(T::DbWeight::get().writes(1 as Weight))
+            .saturating_add(T::DbWeight::get().reads((t as Weight).saturating_mul(1 + a as Weight)))
+            .saturating_add(
+                T::DbWeight::get().writes((t as Weight).saturating_mul(1 + 2 * a as Weight)),
+            )
+    }
 }
@@ -0,0 +1,93 @@
+// Copyright (c) 2021 RBB S.r.l
+// opensource@mintlayer.org
+// SPDX-License-Identifier: MIT
+// Licensed under the MIT License;
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://spdx.org/licenses/MIT
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// Author(s): C. Yap
+
+//! Groth16 proofs over BLS12-381, which spend `SignatureMethod::ZkSnark` outputs.
+//!
+//! Such an output is locked to the hash of a statement: a verifying key along with the public
+//! inputs of the proofs, like ECDSA and BLS outputs are locked to the hash of their key. The
+//! input reveals the statement along with a proof, all in the canonical compressed
+//! serialization of arkworks. The statement is thus fixed when the output is created, and
+//! whoever spends it cannot choose public inputs they happen to have a proof for.
+//!
+//! A proof on its own says nothing about the transaction it is put in. The circuits therefore
+//! take the hash of the input's signature message as their first public input, which the
+//! verifier supplies itself: a proof only spends the input it was made for.
+
+use ark_bls12_381::{Bls12_381, Fr};
+use ark_ff::PrimeField;
+use ark_groth16::{prepare_verifying_key, verify_proof, Proof, VerifyingKey};
+use ark_serialize::CanonicalDeserialize;
+use codec::{Decode, Encode};
+use frame_support::{
+    sp_io::hashing::blake2_256,
+    sp_runtime::traits::{BlakeTwo256, Hash},
+    RuntimeDebug,
+};
+use sp_core::{sp_std::vec::Vec, H256};
+
+/// What an input spending a ZkSnark output provides in place of a signature.
+#[derive(Clone, Encode, Decode, Eq, PartialEq, RuntimeDebug)]
+pub struct ZkProof {
+    /// The verifying key of the statement the output is locked to.
+    pub verifying_key: Vec<u8>,
+    pub proof: Vec<u8>,
+    /// The public inputs of the statement, after the message hash, each a field element of
+    /// 32 bytes.
+    pub public_inputs: Vec<Vec<u8>>,
+}
+
+/// The hash of the statement a ZkSnark output is locked to, see `TransactionOutput::new_zk`.
+pub fn statement_hash(verifying_key: &[u8], public_inputs: &[Vec<u8>]) -> H256 {
+    BlakeTwo256::hash_of(&(verifying_key, public_inputs))
+}
+
+/// The first public input of a proof for `message`.
+pub fn message_input(message: &[u8]) -> Fr {
+    Fr::from_le_bytes_mod_order(&blake2_256(message))
+}
+
+/// Whether `zk_proof` proves the statement of `hash` for `message`.
+pub fn verify(hash: &H256, zk_proof: &ZkProof, message: &[u8]) -> bool {
+    if statement_hash(&zk_proof.verifying_key, &zk_proof.public_inputs) != *hash {
+        return false;
+    }
+    let verifying_key =
+        match VerifyingKey::<Bls12_381>::deserialize(zk_proof.verifying_key.as_slice()) {
+            Ok(verifying_key) => verifying_key,
+            Err(_) => return false,
+        };
+    let proof = match Proof::<Bls12_381>::deserialize(zk_proof.proof.as_slice()) {
+        Ok(proof) => proof,
+        Err(_) => return false,
+    };
+    let public_inputs = match zk_proof
+        .public_inputs
+        .iter()
+        .map(|input| Fr::deserialize(input.as_slice()).ok())
+        .collect::<Option<Vec<_>>>()
+    {
+        Some(inputs) => inputs,
+        None => return false,
+    };
+
+    let mut inputs = Vec::with_capacity(public_inputs.len() + 1);
+    inputs.push(message_input(message));
+    inputs.extend(public_inputs);
+
+    // a key expecting another number of inputs is an error rather than a failed proof
+    verify_proof(&prepare_verifying_key(&verifying_key), &proof, &inputs).unwrap_or(false)
+}